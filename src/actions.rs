@@ -0,0 +1,427 @@
+//! A builder for the parameters of the `PerformActions` command.
+//!
+//! Input sources are declared on an `ActionsBuilder`, which returns a handle
+//! for each, and actions are then added tick by tick. Actions for different
+//! sources share a tick until a source gets a second action, which starts
+//! the next tick; sources without an action in a tick are given a pause, so
+//! every sequence has the same length.
+
+use std::collections::BTreeSet;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use command::{ActionSequence, ActionsParameters, ActionsType, GeneralAction, KeyAction,
+              KeyActionItem, KeyDownAction, KeyUpAction, PauseAction, PointerAction,
+              PointerActionItem, PointerActionParameters, PointerDownAction, PointerMoveAction,
+              PointerType, PointerUpAction, WheelAction, WheelActionItem, WheelScrollAction};
+use common::{Nullable, WebElement};
+use error::{ErrorStatus, WebDriverError, WebDriverResult};
+
+static NEXT_BUILDER_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// A source declared on a builder, identified by the builder's id and the
+/// source's index in it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct SourceHandle {
+    builder: usize,
+    index: usize,
+}
+
+/// A key input source declared with `ActionsBuilder::key`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct KeySource(SourceHandle);
+
+/// A pointer input source declared with `ActionsBuilder::pointer`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PointerSource(SourceHandle);
+
+/// A wheel input source declared with `ActionsBuilder::wheel`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WheelSource(SourceHandle);
+
+enum SourceActions {
+    Key(Vec<KeyActionItem>),
+    Pointer(PointerType, Vec<PointerActionItem>),
+    Wheel(Vec<WheelActionItem>),
+}
+
+impl SourceActions {
+    fn len(&self) -> usize {
+        match *self {
+            SourceActions::Key(ref x) => x.len(),
+            SourceActions::Pointer(_, ref x) => x.len(),
+            SourceActions::Wheel(ref x) => x.len(),
+        }
+    }
+
+    fn push_pause(&mut self, duration: u64) {
+        let pause = GeneralAction::Pause(PauseAction { duration: duration });
+        match *self {
+            SourceActions::Key(ref mut x) => x.push(KeyActionItem::General(pause)),
+            SourceActions::Pointer(_, ref mut x) => x.push(PointerActionItem::General(pause)),
+            SourceActions::Wheel(ref mut x) => x.push(WheelActionItem::General(pause)),
+        }
+    }
+}
+
+struct Source {
+    id: String,
+    actions: SourceActions,
+}
+
+pub struct ActionsBuilder {
+    id: usize,
+    sources: Vec<Source>,
+    ticks: usize,
+    /// The first misuse of the builder, returned by `build`.
+    error: Option<WebDriverError>,
+}
+
+impl Default for ActionsBuilder {
+    fn default() -> ActionsBuilder {
+        ActionsBuilder::new()
+    }
+}
+
+impl ActionsBuilder {
+    /// Create a builder without any input source.
+    pub fn new() -> ActionsBuilder {
+        ActionsBuilder {
+            id: NEXT_BUILDER_ID.fetch_add(1, Ordering::Relaxed),
+            sources: vec![],
+            ticks: 0,
+            error: None,
+        }
+    }
+
+    fn add_source(&mut self, id: &str, actions: SourceActions) -> SourceHandle {
+        self.sources.push(Source {
+            id: id.into(),
+            actions: actions,
+        });
+        SourceHandle {
+            builder: self.id,
+            index: self.sources.len() - 1,
+        }
+    }
+
+    /// Declare a key input source with the given `id`.
+    pub fn key(&mut self, id: &str) -> KeySource {
+        KeySource(self.add_source(id, SourceActions::Key(vec![])))
+    }
+
+    /// Declare a pointer input source of type `pointer_type` with the given
+    /// `id`.
+    pub fn pointer(&mut self, id: &str, pointer_type: PointerType) -> PointerSource {
+        PointerSource(self.add_source(id, SourceActions::Pointer(pointer_type, vec![])))
+    }
+
+    /// Declare a wheel input source with the given `id`.
+    pub fn wheel(&mut self, id: &str) -> WheelSource {
+        WheelSource(self.add_source(id, SourceActions::Wheel(vec![])))
+    }
+
+    /// Pad every source to the current number of ticks.
+    fn pad(&mut self) {
+        let ticks = self.ticks;
+        for source in self.sources.iter_mut() {
+            while source.actions.len() < ticks {
+                source.actions.push_pause(0);
+            }
+        }
+    }
+
+    /// Get the actions of the source of `handle`, starting a new tick if the
+    /// source already has an action in the current one.
+    ///
+    /// A handle declared on another builder is recorded as an error and
+    /// gives `None`.
+    fn source_actions(&mut self, handle: SourceHandle) -> Option<&mut SourceActions> {
+        if handle.builder != self.id || handle.index >= self.sources.len() {
+            if self.error.is_none() {
+                self.error = Some(WebDriverError::new(
+                    ErrorStatus::InvalidArgument,
+                    "Input source was declared on another builder"));
+            }
+            return None;
+        }
+        let index = handle.index;
+        if self.ticks == 0 || self.sources[index].actions.len() == self.ticks {
+            self.pad();
+            self.ticks += 1;
+        }
+        let ticks = self.ticks;
+        let actions = &mut self.sources[index].actions;
+        while actions.len() < ticks - 1 {
+            actions.push_pause(0);
+        }
+        Some(actions)
+    }
+
+    fn key_action(&mut self, source: KeySource, action: KeyAction) -> &mut ActionsBuilder {
+        if let Some(&mut SourceActions::Key(ref mut x)) = self.source_actions(source.0) {
+            x.push(KeyActionItem::Key(action));
+        }
+        self
+    }
+
+    fn pointer_action(&mut self, source: PointerSource, action: PointerAction) -> &mut ActionsBuilder {
+        if let Some(&mut SourceActions::Pointer(_, ref mut x)) = self.source_actions(source.0) {
+            x.push(PointerActionItem::Pointer(action));
+        }
+        self
+    }
+
+    /// Start a new tick, even if the current one has room for more actions.
+    pub fn tick(&mut self) -> &mut ActionsBuilder {
+        self.pad();
+        self.ticks += 1;
+        self
+    }
+
+    /// Add a tick in which every source pauses for `duration` milliseconds.
+    pub fn pause(&mut self, duration: u64) -> &mut ActionsBuilder {
+        self.pad();
+        self.ticks += 1;
+        for source in self.sources.iter_mut() {
+            source.actions.push_pause(duration);
+        }
+        self
+    }
+
+    /// Press the key `value`, which may be a normalized key such as
+    /// `'\u{E008}'` for Shift.
+    pub fn key_down(&mut self, source: KeySource, value: char) -> &mut ActionsBuilder {
+        self.key_action(source, KeyAction::Down(KeyDownAction { value: value }))
+    }
+
+    /// Release the key `value`.
+    pub fn key_up(&mut self, source: KeySource, value: char) -> &mut ActionsBuilder {
+        self.key_action(source, KeyAction::Up(KeyUpAction { value: value }))
+    }
+
+    /// Press `keys` in order and release them in reverse order, e.g.
+    /// `['\u{E009}', 'a']` for Control+A.
+    pub fn chord(&mut self, source: KeySource, keys: &[char]) -> &mut ActionsBuilder {
+        for key in keys {
+            self.key_down(source, *key);
+        }
+        for key in keys.iter().rev() {
+            self.key_up(source, *key);
+        }
+        self
+    }
+
+    /// Type `text` one character at a time.
+    pub fn send_keys(&mut self, source: KeySource, text: &str) -> &mut ActionsBuilder {
+        for key in text.chars() {
+            self.key_down(source, key);
+            self.key_up(source, key);
+        }
+        self
+    }
+
+    /// Press `button`, where 0 is the primary button.
+    pub fn pointer_down(&mut self, source: PointerSource, button: u64) -> &mut ActionsBuilder {
+        self.pointer_action(source, PointerAction::Down(PointerDownAction { button: button }))
+    }
+
+    /// Release `button`.
+    pub fn pointer_up(&mut self, source: PointerSource, button: u64) -> &mut ActionsBuilder {
+        self.pointer_action(source, PointerAction::Up(PointerUpAction { button: button }))
+    }
+
+    /// Cancel the pointer, as when a touch is interrupted.
+    pub fn pointer_cancel(&mut self, source: PointerSource) -> &mut ActionsBuilder {
+        self.pointer_action(source, PointerAction::Cancel)
+    }
+
+    /// Move the pointer to a point in the viewport, over `duration`
+    /// milliseconds if set.
+    pub fn pointer_move(&mut self, source: PointerSource, x: u64, y: u64, duration: Option<u64>)
+                        -> &mut ActionsBuilder {
+        self.pointer_action(source, PointerAction::Move(PointerMoveAction {
+            duration: duration.into(),
+            element: Nullable::Null,
+            x: Nullable::Value(x),
+            y: Nullable::Value(y),
+        }))
+    }
+
+    /// Move the pointer to the center of `element`, over `duration`
+    /// milliseconds if set.
+    pub fn pointer_move_to(&mut self, source: PointerSource, element: &WebElement,
+                           duration: Option<u64>) -> &mut ActionsBuilder {
+        self.pointer_action(source, PointerAction::Move(PointerMoveAction {
+            duration: duration.into(),
+            element: Nullable::Value(element.clone()),
+            x: Nullable::Null,
+            y: Nullable::Null,
+        }))
+    }
+
+    /// Click `element` with the primary button.
+    pub fn click(&mut self, source: PointerSource, element: &WebElement) -> &mut ActionsBuilder {
+        self.pointer_move_to(source, element, None)
+            .pointer_down(source, 0)
+            .pointer_up(source, 0)
+    }
+
+    /// Drag `from` onto `to` with the primary button.
+    pub fn drag_and_drop(&mut self, source: PointerSource, from: &WebElement, to: &WebElement)
+                         -> &mut ActionsBuilder {
+        self.pointer_move_to(source, from, None)
+            .pointer_down(source, 0)
+            .pointer_move_to(source, to, None)
+            .pointer_up(source, 0)
+    }
+
+    /// Scroll by `delta_x` and `delta_y` at a point in the viewport.
+    pub fn scroll(&mut self, source: WheelSource, x: i64, y: i64, delta_x: i64, delta_y: i64,
+                  duration: Option<u64>) -> &mut ActionsBuilder {
+        self.wheel_action(source, None, x, y, delta_x, delta_y, duration)
+    }
+
+    /// Scroll by `delta_x` and `delta_y` at the center of `element`.
+    pub fn scroll_element(&mut self, source: WheelSource, element: &WebElement,
+                          delta_x: i64, delta_y: i64, duration: Option<u64>)
+                          -> &mut ActionsBuilder {
+        self.wheel_action(source, Some(element.clone()), 0, 0, delta_x, delta_y, duration)
+    }
+
+    fn wheel_action(&mut self, source: WheelSource, origin: Option<WebElement>, x: i64, y: i64,
+                    delta_x: i64, delta_y: i64, duration: Option<u64>) -> &mut ActionsBuilder {
+        if let Some(&mut SourceActions::Wheel(ref mut actions)) = self.source_actions(source.0) {
+            actions.push(WheelActionItem::Wheel(WheelAction::Scroll(WheelScrollAction {
+                duration: duration.into(),
+                origin: origin.into(),
+                x: x,
+                y: y,
+                delta_x: delta_x,
+                delta_y: delta_y,
+            })));
+        }
+        self
+    }
+
+    /// Build the parameters, checking that source ids are unique and that
+    /// every source used was declared on this builder.
+    pub fn build(mut self) -> WebDriverResult<ActionsParameters> {
+        if let Some(err) = self.error.take() {
+            return Err(err);
+        }
+        self.pad();
+        let mut ids = BTreeSet::new();
+        for source in self.sources.iter() {
+            if !ids.insert(source.id.clone()) {
+                return Err(WebDriverError::new(
+                    ErrorStatus::InvalidArgument,
+                    format!("Duplicate input source id {}", source.id)));
+            }
+        }
+
+        let actions = self.sources.into_iter().map(|source| {
+            let actions = match source.actions {
+                SourceActions::Key(x) => ActionsType::Key(x),
+                SourceActions::Pointer(pointer_type, x) => {
+                    ActionsType::Pointer(PointerActionParameters { pointer_type: pointer_type }, x)
+                },
+                SourceActions::Wheel(x) => ActionsType::Wheel(x),
+            };
+            ActionSequence {
+                id: Nullable::Value(source.id),
+                actions: actions,
+            }
+        }).collect();
+        Ok(ActionsParameters {
+            actions: actions
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use command::{ActionsParameters, Parameters, PointerType};
+    use common::WebElement;
+    use error::ErrorStatus;
+    use rustc_serialize::json::{Json, ToJson};
+    use super::ActionsBuilder;
+
+    #[test]
+    fn test_ticks() {
+        let mut builder = ActionsBuilder::new();
+        let keyboard = builder.key("keyboard");
+        let mouse = builder.pointer("mouse", PointerType::Mouse);
+        let wheel = builder.wheel("wheel");
+        builder.chord(keyboard, &['\u{E009}', 'a'])
+            .pointer_move(mouse, 10, 20, Some(100))
+            .pause(50)
+            .scroll(wheel, 0, 0, 0, 100, None);
+        let parameters = builder.build().unwrap();
+
+        let expected = Json::from_str(r#"{"actions": [
+            {"id": "keyboard", "type": "key", "actions": [
+                {"type": "keyDown", "value": "\uE009"},
+                {"type": "keyDown", "value": "a"},
+                {"type": "keyUp", "value": "a"},
+                {"type": "keyUp", "value": "\uE009"},
+                {"type": "pause", "duration": 50},
+                {"type": "pause", "duration": 0}]},
+            {"id": "mouse", "type": "pointer", "parameters": {"pointerType": "mouse"}, "actions": [
+                {"type": "pause", "duration": 0},
+                {"type": "pause", "duration": 0},
+                {"type": "pause", "duration": 0},
+                {"type": "pointerMove", "x": 10, "y": 20, "duration": 100},
+                {"type": "pause", "duration": 50},
+                {"type": "pause", "duration": 0}]},
+            {"id": "wheel", "type": "wheel", "actions": [
+                {"type": "pause", "duration": 0},
+                {"type": "pause", "duration": 0},
+                {"type": "pause", "duration": 0},
+                {"type": "pause", "duration": 0},
+                {"type": "pause", "duration": 50},
+                {"type": "scroll", "origin": "viewport",
+                 "x": 0, "y": 0, "deltaX": 0, "deltaY": 100}]}]}"#).unwrap();
+        assert_eq!(parameters.to_json().to_string(), expected.to_string());
+        assert!(ActionsParameters::from_json(&expected).unwrap() == parameters);
+    }
+
+    #[test]
+    fn test_drag_and_drop() {
+        let mut builder = ActionsBuilder::new();
+        let keyboard = builder.key("keyboard");
+        let mouse = builder.pointer("mouse", PointerType::Mouse);
+        builder.drag_and_drop(mouse, &WebElement::new("a".into()), &WebElement::new("b".into()))
+            .tick()
+            .key_down(keyboard, 'x');
+        let json = builder.build().unwrap().to_json();
+        let sources = json.find("actions").unwrap().as_array().unwrap();
+        let types = |index: usize| -> Vec<String> {
+            sources[index].find("actions").unwrap().as_array().unwrap().iter()
+                .map(|x| x.find("type").unwrap().as_string().unwrap().into())
+                .collect()
+        };
+        assert_eq!(types(0), vec!["pause", "pause", "pause", "pause", "keyDown"]);
+        assert_eq!(types(1), vec!["pointerMove", "pointerDown", "pointerMove", "pointerUp", "pause"]);
+        assert_eq!(sources[1].find_path(&["actions"]).unwrap()[2].find("element"),
+                   Some(&WebElement::new("b".into()).to_json()));
+    }
+
+    #[test]
+    fn test_duplicate_ids() {
+        let mut builder = ActionsBuilder::new();
+        builder.key("input");
+        builder.wheel("input");
+        assert_eq!(builder.build().err().unwrap().error, ErrorStatus::InvalidArgument);
+    }
+
+    #[test]
+    fn test_foreign_source() {
+        let mut other = ActionsBuilder::new();
+        let keyboard = other.key("keyboard");
+
+        let mut builder = ActionsBuilder::new();
+        builder.key("keyboard");
+        builder.key_down(keyboard, 'a');
+        assert_eq!(builder.build().err().unwrap().error, ErrorStatus::InvalidArgument);
+    }
+}
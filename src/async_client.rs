@@ -0,0 +1,99 @@
+//! A client sending WebDriver commands without blocking.
+
+use std::sync::{Arc, Mutex};
+
+use futures::{future, Future, Stream};
+use hyper::method::Method;
+use hyper_async::{self, Body, Request};
+use hyper_async::client::HttpConnector;
+
+use async_server::WebDriverFuture;
+use client::{decode_response, update_session};
+use command::{WebDriverCommand, WebDriverMessage};
+use error::{ErrorStatus, WebDriverError};
+use httpapi::{WebDriverHttpApi, WebDriverExtensionRoute, VoidWebDriverExtensionRoute};
+
+/// An asynchronous counterpart of `client::WebDriverClient`.
+///
+/// Commands may be sent concurrently; each is sent in the session that is
+/// current when `send` is called. The client must be used from within a
+/// tokio runtime.
+pub struct AsyncWebDriverClient<U: WebDriverExtensionRoute=VoidWebDriverExtensionRoute> {
+    client: hyper_async::Client<HttpConnector>,
+    base_url: String,
+    api: WebDriverHttpApi<U>,
+    session_id: Arc<Mutex<Option<String>>>,
+}
+
+impl <U: 'static + WebDriverExtensionRoute> AsyncWebDriverClient<U>
+    where U::Command: Send + 'static
+{
+    /// Create a client for the remote end at `base_url`, e.g.
+    /// `http://127.0.0.1:4444`, which also serves `extension_routes`.
+    pub fn new(base_url: &str, extension_routes: &[(Method, &str, U)]) -> AsyncWebDriverClient<U> {
+        AsyncWebDriverClient {
+            client: hyper_async::Client::new(),
+            base_url: base_url.trim_right_matches('/').to_owned(),
+            api: WebDriverHttpApi::new(extension_routes),
+            session_id: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    pub fn session_id(&self) -> Option<String> {
+        self.session_id.lock().unwrap().clone()
+    }
+
+    /// Use an existing session, e.g. one created by another client.
+    pub fn attach(&self, session_id: String) {
+        *self.session_id.lock().unwrap() = Some(session_id);
+    }
+
+    /// Send a command to the remote end, in the current session if there is
+    /// one, resolving to its response.
+    pub fn send(&self, command: WebDriverCommand<U::Command>) -> WebDriverFuture {
+        let session_id = match command {
+            WebDriverCommand::NewSession(_) | WebDriverCommand::Status => None,
+            _ => self.session_id(),
+        };
+        let msg = WebDriverMessage::new(session_id, command);
+        let (method, path, body) = match self.api.encode_request(&msg) {
+            Ok(x) => x,
+            Err(err) => return Box::new(future::err(err)),
+        };
+        let url = format!("{}{}", self.base_url, path);
+        debug!("Sending request {} {}", method, url);
+
+        let request = Request::builder()
+            .method(method.as_ref())
+            .uri(&*url)
+            .header("Content-Type", "application/json; charset=utf-8")
+            .body(body.map(Body::from).unwrap_or_else(Body::empty));
+        let request = match request {
+            Ok(x) => x,
+            Err(err) => return Box::new(future::err(
+                WebDriverError::new(ErrorStatus::InvalidArgument, err.to_string()))),
+        };
+
+        let session_id = self.session_id.clone();
+        Box::new(self.client.request(request)
+                 .and_then(|resp| {
+                     let status = resp.status().as_u16();
+                     resp.into_body().concat2().map(move |body| (status, body))
+                 })
+                 .map_err(|err| {
+                     WebDriverError::new(ErrorStatus::UnknownError, err.to_string())
+                         .with_source(err)
+                 })
+                 .and_then(move |(status, body)| {
+                     let body = String::from_utf8_lossy(&body);
+                     debug!("Got response {} {}", status, body);
+                     let response = try!(decode_response(&msg.command, status, &body));
+                     update_session(&mut session_id.lock().unwrap(), &response);
+                     Ok(response)
+                 }))
+    }
+}
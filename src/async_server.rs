@@ -0,0 +1,575 @@
+//! A WebDriver server for handlers that answer commands asynchronously.
+//!
+//! Requests are served on a tokio runtime and commands run concurrently, so
+//! a handler talking to the browser over asynchronous IPC doesn't need a
+//! thread per command. The server manages sessions, timeouts, input state
+//! and user prompts like the blocking server, but implicit waits are left to
+//! the handler.
+
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use futures::{future, Future, Poll, Stream};
+use hyper::method::Method;
+use hyper_async::{self, Body, Request, Response, Server};
+use hyper_async::service::service_fn;
+
+use capabilities::{CapabilitiesRegistry, UnhandledPromptBehavior};
+use command::{ActionsParameters, TimeoutsParameters, WebDriverCommand, WebDriverMessage};
+use error::{set_backtrace_capture, BacktraceCapture, ErrorStatus, StacktraceReporting,
+            WebDriverError, WebDriverResult};
+use httpapi::{WebDriverHttpApi, WebDriverExtensionRoute, VoidWebDriverExtensionRoute};
+use input::{InputState, SourceAction};
+use response::{CloseWindowResponse, WebDriverResponse};
+use server::{check_session, handles_user_prompts, needs_prompt_text, new_session_settings,
+             prompt_closed, prompt_command, prompt_result, prompt_text, ServerConfig, Session,
+             Timeouts};
+
+pub type WebDriverFuture = Box<Future<Item=WebDriverResponse, Error=WebDriverError> + Send>;
+
+type BoxFuture<T> = Box<Future<Item=T, Error=WebDriverError> + Send>;
+
+pub trait AsyncWebDriverHandler<U: WebDriverExtensionRoute=VoidWebDriverExtensionRoute> : Send + Sync + 'static {
+    fn handle_command(&self, session: &Option<Session>, msg: WebDriverMessage<U>) -> WebDriverFuture;
+    fn delete_session(&self, session: &Option<Session>);
+
+    /// Run a `ReleaseActions` command, performing `actions` in order, as
+    /// for `WebDriverHandler::release_actions`.
+    fn release_actions(&self, session: &Option<Session>, msg: WebDriverMessage<U>,
+                       _: Vec<SourceAction>) -> WebDriverFuture {
+        self.handle_command(session, msg)
+    }
+
+    /// Report whether a user prompt is currently open.
+    ///
+    /// If it is, the server applies the session's `unhandledPromptBehavior`
+    /// before running commands that handle user prompts, as the blocking
+    /// server does.
+    fn is_user_prompt_open(&self, _: &Option<Session>)
+                           -> Box<Future<Item=bool, Error=WebDriverError> + Send> {
+        Box::new(future::ok(false))
+    }
+}
+
+/// The current session, and whether a `NewSession` command is running.
+struct SessionSlot {
+    session: Option<Session>,
+    creating: bool,
+}
+
+struct AsyncDispatcher<T, U: WebDriverExtensionRoute> {
+    handler: T,
+    api: Mutex<WebDriverHttpApi<U>>,
+    capabilities: Mutex<CapabilitiesRegistry>,
+    session: Mutex<SessionSlot>,
+    backtrace_capture: BacktraceCapture,
+    stacktrace_reporting: StacktraceReporting,
+}
+
+impl <T: AsyncWebDriverHandler<U>, U: 'static + WebDriverExtensionRoute> AsyncDispatcher<T, U> {
+    fn dispatch(dispatcher: &Arc<AsyncDispatcher<T, U>>,
+                mut msg: WebDriverMessage<U>) -> WebDriverFuture {
+        // The session is checked and reserved under the lock, so that
+        // concurrent commands see each other's changes.
+        let mut slot = dispatcher.session.lock().unwrap();
+        if let Err(err) = check_session(&slot.session, &msg) {
+            return Box::new(future::err(err));
+        }
+
+        let mut release = None;
+        let change = match msg.command {
+            WebDriverCommand::NewSession(ref mut parameters) => {
+                if slot.creating {
+                    return Box::new(future::err(WebDriverError::new(
+                        ErrorStatus::SessionNotCreated,
+                        "Session is already being created")));
+                }
+                let capabilities = dispatcher.capabilities.lock().unwrap();
+                match new_session_settings(&capabilities, parameters) {
+                    Ok((timeouts, prompt_behavior)) => {
+                        slot.creating = true;
+                        SessionChange::NewSession(timeouts, prompt_behavior)
+                    },
+                    Err(err) => return Box::new(future::err(err)),
+                }
+            },
+            WebDriverCommand::GetTimeouts => {
+                let session = slot.session.as_ref().expect("Session was checked");
+                return Box::new(future::ok(
+                    WebDriverResponse::Timeouts(session.timeouts.to_response())));
+            },
+            WebDriverCommand::SetTimeouts(ref parameters) => {
+                SessionChange::Timeouts(parameters.clone())
+            },
+            WebDriverCommand::PerformActions(ref parameters) => {
+                // Adding the sources reserves their types for this command
+                let session = slot.session.as_mut().expect("Session was checked");
+                if let Err(err) = session.input_state.add_sources(parameters) {
+                    return Box::new(future::err(err));
+                }
+                SessionChange::PerformActions(parameters.clone())
+            },
+            WebDriverCommand::ReleaseActions => {
+                let session = slot.session.as_ref().expect("Session was checked");
+                release = Some(session.input_state.release_actions());
+                SessionChange::ReleaseActions
+            },
+            _ => SessionChange::None
+        };
+        let session = slot.session.clone();
+        drop(slot);
+
+        let prompts = AsyncDispatcher::handle_user_prompts(dispatcher, &session, &msg.command);
+        let handler = dispatcher.clone();
+        let dispatcher = dispatcher.clone();
+        Box::new(prompts
+                 .and_then(move |_| match release {
+                     Some(actions) => handler.handler.release_actions(&session, msg, actions),
+                     None => handler.handler.handle_command(&session, msg),
+                 })
+                 .then(move |resp| dispatcher.update_session(resp, change)))
+    }
+
+    /// Apply the session's `unhandledPromptBehavior` if a user prompt is
+    /// open and `command` handles user prompts.
+    fn handle_user_prompts(dispatcher: &Arc<AsyncDispatcher<T, U>>,
+                           session: &Option<Session>,
+                           command: &WebDriverCommand<U::Command>)
+                           -> BoxFuture<()> {
+        let (session_id, behavior) = match *session {
+            Some(ref session) if handles_user_prompts(command) => {
+                (session.id.clone(), session.unhandled_prompt_behavior.clone())
+            },
+            _ => return Box::new(future::ok(()))
+        };
+
+        let dispatcher = dispatcher.clone();
+        let session = session.clone();
+        Box::new(dispatcher.handler.is_user_prompt_open(&session).and_then(move |open| {
+            if !open {
+                return Box::new(future::ok(())) as BoxFuture<()>;
+            }
+
+            let text = if needs_prompt_text(&behavior) {
+                let msg = WebDriverMessage::new(Some(session_id.clone()),
+                                                WebDriverCommand::GetAlertText);
+                Box::new(dispatcher.handler.handle_command(&session, msg).then(prompt_text))
+                    as BoxFuture<Option<String>>
+            } else {
+                Box::new(future::ok(Some(String::new())))
+            };
+
+            Box::new(text.and_then(move |text| {
+                let text = match text {
+                    Some(text) => text,
+                    // The prompt was closed in the meantime
+                    None => return Box::new(future::ok(())) as BoxFuture<()>,
+                };
+                let closed = match prompt_command(&behavior) {
+                    Some(command) => {
+                        let msg = WebDriverMessage::new(Some(session_id), command);
+                        Box::new(dispatcher.handler.handle_command(&session, msg)
+                                 .then(prompt_closed)) as BoxFuture<()>
+                    },
+                    None => Box::new(future::ok(())),
+                };
+                Box::new(closed.and_then(move |_| prompt_result(&behavior, text)))
+            }))
+        }))
+    }
+
+    /// Apply the effect of a command's response on the session.
+    fn update_session(&self,
+                      resp: Result<WebDriverResponse, WebDriverError>,
+                      change: SessionChange)
+                      -> Result<WebDriverResponse, WebDriverError> {
+        let mut slot = self.session.lock().unwrap();
+        if let SessionChange::NewSession(..) = change {
+            slot.creating = false;
+        }
+        let delete = match resp {
+            Ok(WebDriverResponse::NewSession(ref x)) => {
+                if let SessionChange::NewSession(timeouts, prompt_behavior) = change {
+                    slot.session = Some(Session::new(x.sessionId.clone(), timeouts,
+                                                     prompt_behavior));
+                }
+                false
+            },
+            Ok(WebDriverResponse::CloseWindow(CloseWindowResponse { ref window_handles })) => {
+                window_handles.is_empty()
+            },
+            Ok(WebDriverResponse::DeleteSession) => true,
+            Ok(_) => {
+                if let Some(session) = slot.session.as_mut() {
+                    match change {
+                        SessionChange::Timeouts(parameters) => {
+                            session.timeouts.update(&parameters)
+                        },
+                        SessionChange::PerformActions(parameters) => {
+                            try!(session.input_state.update(parameters))
+                        },
+                        SessionChange::ReleaseActions => session.input_state = InputState::new(),
+                        _ => {}
+                    }
+                }
+                false
+            },
+            Err(ref x) => x.delete_session,
+        };
+        if delete {
+            debug!("Deleting session");
+            self.handler.delete_session(&slot.session);
+            slot.session = None;
+        }
+        resp
+    }
+}
+
+/// A change to the session made once the handler succeeded.
+enum SessionChange {
+    None,
+    NewSession(Timeouts, UnhandledPromptBehavior),
+    Timeouts(TimeoutsParameters),
+    PerformActions(ActionsParameters),
+    ReleaseActions,
+}
+
+fn handle_request<T, U>(dispatcher: Arc<AsyncDispatcher<T, U>>, req: Request<Body>)
+                        -> Box<Future<Item=Response<Body>, Error=hyper_async::Error> + Send>
+    where T: AsyncWebDriverHandler<U>,
+          U: 'static + WebDriverExtensionRoute
+{
+    let method = req.method().as_str().parse::<Method>();
+    let path = req.uri().path().to_owned();
+
+    Box::new(req.into_body().concat2().and_then(move |body| {
+        set_backtrace_capture(dispatcher.backtrace_capture);
+        let body = String::from_utf8_lossy(&body);
+        debug!("Got request {:?} {}", method, path);
+        let msg = match method {
+            Ok(method) => dispatcher.api.lock().unwrap().decode_request(method, &path, &body),
+            Err(_) => Err(WebDriverError::new(ErrorStatus::UnknownMethod,
+                                              format!("Unknown method for {}", path))),
+        };
+        let resp = match msg {
+            Ok(msg) => AsyncDispatcher::dispatch(&dispatcher, msg),
+            Err(err) => Box::new(future::err(err)),
+        };
+
+        resp.then(move |resp| {
+            let (status, body) = match resp {
+                Ok(response) => (200, response.to_json_string()),
+                Err(err) => {
+                    let err = err.with_backtrace_capture(dispatcher.backtrace_capture);
+                    (err.http_status().to_u16(),
+                     err.to_json_with(dispatcher.stacktrace_reporting).to_string())
+                },
+            };
+            debug!("Returning status {} body {}", status, body);
+            Ok(Response::builder()
+               .status(status)
+               .header("Content-Type", "application/json; charset=utf-8")
+               .header("Cache-Control", "no-cache")
+               .body(Body::from(body))
+               .unwrap())
+        })
+    }))
+}
+
+/// A running server, which completes if serving fails.
+pub struct AsyncServer {
+    local_addr: SocketAddr,
+    server: Box<Future<Item=(), Error=()> + Send>,
+}
+
+impl AsyncServer {
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+}
+
+impl Future for AsyncServer {
+    type Item = ();
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<(), ()> {
+        self.server.poll()
+    }
+}
+
+/// Bind a server to `address`; it runs once spawned on a tokio runtime.
+///
+/// The `implicit_wait` setting of `config` isn't supported, and gives an
+/// `unsupported operation` error.
+pub fn serve<T, U>(address: SocketAddr,
+                   handler: T,
+                   extension_routes: &[(Method, &str, U)],
+                   config: ServerConfig)
+                   -> WebDriverResult<AsyncServer>
+    where T: AsyncWebDriverHandler<U>,
+          U: 'static + WebDriverExtensionRoute
+{
+    if config.implicit_wait.is_some() {
+        return Err(WebDriverError::new(ErrorStatus::UnsupportedOperation,
+                                       "Implicit waits are not supported by the async server"));
+    }
+
+    let dispatcher = Arc::new(AsyncDispatcher {
+        handler: handler,
+        api: Mutex::new(WebDriverHttpApi::new(extension_routes)),
+        capabilities: Mutex::new(config.capabilities),
+        session: Mutex::new(SessionSlot {
+            session: None,
+            creating: false,
+        }),
+        backtrace_capture: config.backtrace_capture,
+        stacktrace_reporting: config.stacktrace_reporting,
+    });
+
+    let server = try!(Server::try_bind(&address).map_err(|err| {
+        WebDriverError::new(ErrorStatus::UnknownError, err.to_string()).with_source(err)
+    })).serve(move || {
+        let dispatcher = dispatcher.clone();
+        service_fn(move |req| handle_request(dispatcher.clone(), req))
+    });
+    Ok(AsyncServer {
+        local_addr: server.local_addr(),
+        server: Box::new(server.map_err(|err| error!("Server failed: {}", err))),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use async_client::AsyncWebDriverClient;
+    use command::{Parameters, TimeoutsParameters, VoidWebDriverExtensionCommand, WebDriverCommand,
+                  WebDriverMessage};
+    use common::Nullable;
+    use error::{ErrorStatus, WebDriverError};
+    use futures::{future, Future};
+    use futures::sync::oneshot;
+    use httpapi::VoidWebDriverExtensionRoute;
+    use input::SourceAction;
+    use response::{NewSessionResponse, ValueResponse, WebDriverResponse};
+    use rustc_serialize::json::{Json, ToJson};
+    use server::{ImplicitWait, ServerConfig, Session};
+    use std::sync::{Arc, Mutex};
+    use std::sync::mpsc::{channel, Sender};
+    use std::time::Duration;
+    use tokio::runtime::Runtime;
+    use super::{serve, AsyncServer, AsyncWebDriverHandler, WebDriverFuture};
+
+    #[derive(Default)]
+    struct TestHandler {
+        prompt: Mutex<bool>,
+        /// Told when a `NewSession` command starts.
+        new_session_started: Mutex<Option<Sender<()>>>,
+        /// Completes the next `NewSession` command.
+        new_session_done: Mutex<Option<oneshot::Receiver<()>>>,
+        released: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl AsyncWebDriverHandler for TestHandler {
+        fn handle_command(&self, _: &Option<Session>, msg: WebDriverMessage)
+                          -> WebDriverFuture {
+            match msg.command {
+                WebDriverCommand::NewSession(_) => {
+                    if let Some(started) = self.new_session_started.lock().unwrap().take() {
+                        started.send(()).unwrap();
+                    }
+                    let done = self.new_session_done.lock().unwrap().take();
+                    let response = WebDriverResponse::NewSession(NewSessionResponse::new(
+                        "session-1".into(), Json::from_str("{}").unwrap()));
+                    match done {
+                        Some(done) => Box::new(done.then(|_| Ok(response))),
+                        None => Box::new(future::ok(response)),
+                    }
+                },
+                WebDriverCommand::DeleteSession => {
+                    Box::new(future::ok(WebDriverResponse::DeleteSession))
+                },
+                WebDriverCommand::SetTimeouts(_) => Box::new(future::ok(WebDriverResponse::Void)),
+                WebDriverCommand::GetAlertText => {
+                    Box::new(future::ok(WebDriverResponse::Generic(
+                        ValueResponse::new("hello".to_json()))))
+                },
+                WebDriverCommand::DismissAlert => {
+                    *self.prompt.lock().unwrap() = false;
+                    Box::new(future::ok(WebDriverResponse::Void))
+                },
+                WebDriverCommand::PerformActions(ref parameters) => {
+                    // Pressing "c" fails
+                    let fails = parameters.to_json().to_string().contains(r#""value":"c""#);
+                    Box::new(if fails {
+                        future::err(WebDriverError::new(ErrorStatus::UnknownError, "Key failed"))
+                    } else {
+                        future::ok(WebDriverResponse::Void)
+                    })
+                },
+                WebDriverCommand::GetTitle if !*self.prompt.lock().unwrap() => {
+                    Box::new(future::ok(WebDriverResponse::Generic(
+                        ValueResponse::new("Title".to_json()))))
+                },
+                _ => Box::new(future::err(WebDriverError::new(ErrorStatus::NoSuchElement,
+                                                              "Nothing here"))),
+            }
+        }
+
+        fn delete_session(&self, _: &Option<Session>) {}
+
+        fn release_actions(&self, _: &Option<Session>, _: WebDriverMessage,
+                           actions: Vec<SourceAction>) -> WebDriverFuture {
+            *self.released.lock().unwrap() = actions.into_iter().map(|x| x.id).collect();
+            Box::new(future::ok(WebDriverResponse::Void))
+        }
+
+        fn is_user_prompt_open(&self, _: &Option<Session>)
+                               -> Box<Future<Item=bool, Error=WebDriverError> + Send> {
+            Box::new(future::ok(*self.prompt.lock().unwrap()))
+        }
+    }
+
+    fn start(runtime: &mut Runtime, handler: TestHandler)
+             -> AsyncWebDriverClient<VoidWebDriverExtensionRoute> {
+        let server: AsyncServer = serve("127.0.0.1:0".parse().unwrap(),
+                                        handler,
+                                        &[] as &[(_, _, VoidWebDriverExtensionRoute)],
+                                        ServerConfig::default()).unwrap();
+        let client = AsyncWebDriverClient::new(&format!("http://{}", server.local_addr()), &[]);
+        runtime.spawn(server);
+        client
+    }
+
+    fn new_session() -> WebDriverCommand<VoidWebDriverExtensionCommand> {
+        WebDriverCommand::NewSession(
+            Parameters::from_json(&Json::from_str("{}").unwrap()).unwrap())
+    }
+
+    #[test]
+    fn test_async_session() {
+        let mut runtime = Runtime::new().unwrap();
+        let client = start(&mut runtime, TestHandler::default());
+
+        runtime.block_on(client.send(new_session())).unwrap();
+        assert_eq!(client.session_id(), Some("session-1".into()));
+
+        let timeouts = TimeoutsParameters {
+            script: Some(Nullable::Null),
+            page_load: None,
+            implicit: Some(1000),
+        };
+        runtime.block_on(client.send(WebDriverCommand::SetTimeouts(timeouts))).unwrap();
+        match runtime.block_on(client.send(WebDriverCommand::GetTimeouts)).unwrap() {
+            WebDriverResponse::Timeouts(x) => {
+                assert_eq!(x.script, Nullable::Null);
+                assert_eq!(x.implicit, 1000);
+            },
+            x => panic!("Unexpected response {:?}", x)
+        }
+
+        let err = runtime.block_on(client.send(WebDriverCommand::GetPageSource)).err().unwrap();
+        assert_eq!(err.error, ErrorStatus::NoSuchElement);
+
+        runtime.block_on(client.send(WebDriverCommand::DeleteSession)).unwrap();
+        assert_eq!(client.session_id(), None);
+        let err = runtime.block_on(client.send(WebDriverCommand::GetTitle)).err().unwrap();
+        assert_eq!(err.error, ErrorStatus::InvalidSessionId);
+    }
+
+    #[test]
+    fn test_concurrent_new_session() {
+        let mut runtime = Runtime::new().unwrap();
+        let (started_send, started_recv) = channel();
+        let (done_send, done_recv) = oneshot::channel();
+        let handler = TestHandler {
+            new_session_started: Mutex::new(Some(started_send)),
+            new_session_done: Mutex::new(Some(done_recv)),
+            ..TestHandler::default()
+        };
+        let client = start(&mut runtime, handler);
+
+        let (first_send, first_recv) = oneshot::channel();
+        runtime.spawn(client.send(new_session()).then(|resp| {
+            first_send.send(resp.is_ok()).unwrap();
+            Ok(())
+        }));
+        started_recv.recv_timeout(Duration::from_secs(10)).unwrap();
+
+        let err = runtime.block_on(client.send(new_session())).err().unwrap();
+        assert_eq!(err.error, ErrorStatus::SessionNotCreated);
+
+        done_send.send(()).unwrap();
+        assert!(first_recv.wait().unwrap());
+        assert_eq!(client.session_id(), Some("session-1".into()));
+        let err = runtime.block_on(client.send(new_session())).err().unwrap();
+        assert_eq!(err.error, ErrorStatus::SessionNotCreated);
+    }
+
+    #[test]
+    fn test_user_prompts() {
+        let mut runtime = Runtime::new().unwrap();
+        let handler = TestHandler {
+            prompt: Mutex::new(true),
+            ..TestHandler::default()
+        };
+        let client = start(&mut runtime, handler);
+        runtime.block_on(client.send(new_session())).unwrap();
+
+        // The default behavior dismisses the prompt and reports it.
+        let err = runtime.block_on(client.send(WebDriverCommand::GetTitle)).err().unwrap();
+        assert_eq!(err.error, ErrorStatus::UnexpectedAlertOpen);
+        assert_eq!(err.data.unwrap().find("text"), Some(&"hello".to_json()));
+
+        match runtime.block_on(client.send(WebDriverCommand::GetTitle)).unwrap() {
+            WebDriverResponse::Generic(x) => assert_eq!(x.value, "Title".to_json()),
+            x => panic!("Unexpected response {:?}", x)
+        }
+    }
+
+    #[test]
+    fn test_input_state() {
+        let mut runtime = Runtime::new().unwrap();
+        let released = Arc::new(Mutex::new(vec![]));
+        let client = start(&mut runtime, TestHandler {
+            released: released.clone(),
+            ..TestHandler::default()
+        });
+        runtime.block_on(client.send(new_session())).unwrap();
+
+        let actions = |body: &str| {
+            WebDriverCommand::PerformActions(
+                Parameters::from_json(&Json::from_str(body).unwrap()).unwrap())
+        };
+        runtime.block_on(client.send(actions(r#"{"actions": [
+            {"type": "key", "id": "keyboard", "actions": [
+                {"type": "keyDown", "value": "a"}, {"type": "pause"}]},
+            {"type": "key", "id": "other", "actions": [
+                {"type": "pause"}, {"type": "keyDown", "value": "b"}]}]}"#))).unwrap();
+
+        // The state isn't updated with the actions of a command that failed
+        let err = runtime.block_on(client.send(actions(r#"{"actions": [
+            {"type": "key", "id": "keyboard", "actions": [
+                {"type": "keyUp", "value": "a"},
+                {"type": "keyDown", "value": "c"}]}]}"#))).err().unwrap();
+        assert_eq!(err.error, ErrorStatus::UnknownError);
+
+        let err = runtime.block_on(client.send(actions(r#"{"actions": [
+            {"type": "pointer", "id": "keyboard", "actions": []}]}"#))).err().unwrap();
+        assert_eq!(err.error, ErrorStatus::InvalidArgument);
+
+        runtime.block_on(client.send(WebDriverCommand::ReleaseActions)).unwrap();
+        assert_eq!(*released.lock().unwrap(), vec!["other", "keyboard"]);
+    }
+
+    #[test]
+    fn test_implicit_wait_unsupported() {
+        let config = ServerConfig {
+            implicit_wait: Some(ImplicitWait::new(Duration::from_millis(10))),
+            ..ServerConfig::default()
+        };
+        let err = serve("127.0.0.1:0".parse().unwrap(),
+                        TestHandler::default(),
+                        &[] as &[(_, _, VoidWebDriverExtensionRoute)],
+                        config).err().unwrap();
+        assert_eq!(err.error, ErrorStatus::UnsupportedOperation);
+    }
+}
@@ -0,0 +1,582 @@
+use rustc_serialize::json::{Json, ToJson};
+use std::any::Any;
+use std::collections::BTreeMap;
+
+use command::NewSessionParameters;
+use error::{WebDriverResult, WebDriverError, ErrorStatus};
+
+/// Capabilities defined by the specification. Any other capability
+/// name must carry a vendor prefix.
+pub static STANDARD_CAPABILITIES: &'static [&'static str] = &[
+    "acceptInsecureCerts",
+    "browserName",
+    "browserVersion",
+    "pageLoadStrategy",
+    "platformName",
+    "proxy",
+    "setWindowRect",
+    "strictFileInteractability",
+    "timeouts",
+    "unhandledPromptBehavior",
+];
+
+type CapabilityParser = Box<Fn(&str, &Json) -> WebDriverResult<Box<Any + Send>> + Send>;
+
+/// Parsers for vendor-prefixed capabilities such as `moz:firefoxOptions`.
+///
+/// The registry is consulted when a new session is requested. Each
+/// capability whose prefix has a registered parser is validated and
+/// parsed before the handler sees the command; capabilities with an
+/// unregistered prefix are passed through unchanged. Capabilities without
+/// a prefix that the specification doesn't define are rejected, unless
+/// strict checking is turned off with `set_strict`.
+pub struct CapabilitiesRegistry {
+    parsers: BTreeMap<String, CapabilityParser>,
+    strict: bool,
+}
+
+impl CapabilitiesRegistry {
+    pub fn new() -> CapabilitiesRegistry {
+        CapabilitiesRegistry {
+            parsers: BTreeMap::new(),
+            strict: true,
+        }
+    }
+
+    /// Reject capabilities without a vendor prefix that aren't in
+    /// `STANDARD_CAPABILITIES`. This is on by default; turn it off for
+    /// legacy clients that send other unprefixed capabilities.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    /// Register a parser for all capabilities starting with `prefix:`.
+    ///
+    /// The parser is called with the full capability name and its value,
+    /// and rejects invalid values by returning an error.
+    pub fn register<T, F>(&mut self, prefix: &str, parser: F)
+        where T: Any + Send,
+              F: Fn(&str, &Json) -> WebDriverResult<T> + Send + 'static
+    {
+        self.parsers.insert(prefix.to_string(), Box::new(move |name: &str, value: &Json| {
+            let parsed = try!(parser(name, value));
+            Ok(Box::new(parsed) as Box<Any + Send>)
+        }));
+    }
+
+    pub fn process(&self, parameters: &mut NewSessionParameters) -> WebDriverResult<()> {
+        let mut vendor = VendorCapabilities::default();
+        // Required capabilities are processed last so that they take
+        // precedence over desired ones.
+        for capabilities in [&parameters.desired, &parameters.required].iter() {
+            for (name, value) in capabilities.iter() {
+                match name.find(':') {
+                    Some(index) => {
+                        if let Some(parser) = self.parsers.get(&name[..index]) {
+                            vendor.values.insert(name.clone(),
+                                                 (value.clone(), try!(parser(name, value))));
+                        }
+                    },
+                    None => {
+                        if self.strict && !STANDARD_CAPABILITIES.contains(&&**name) {
+                            return Err(WebDriverError::new(
+                                ErrorStatus::InvalidArgument,
+                                format!("Unrecognised capability {}", name)));
+                        }
+                    }
+                }
+            }
+        }
+        parameters.vendor = vendor;
+        Ok(())
+    }
+}
+
+impl Default for CapabilitiesRegistry {
+    fn default() -> CapabilitiesRegistry {
+        CapabilitiesRegistry::new()
+    }
+}
+
+/// Vendor-prefixed capabilities parsed by a `CapabilitiesRegistry`.
+#[derive(Default)]
+pub struct VendorCapabilities {
+    /// The raw value of each capability alongside its parsed value.
+    values: BTreeMap<String, (Json, Box<Any + Send>)>,
+}
+
+impl VendorCapabilities {
+    pub fn contains(&self, name: &str) -> bool {
+        self.values.contains_key(name)
+    }
+
+    /// Get the parsed value of the capability `name`, if it was parsed
+    /// into a `T`.
+    pub fn get<T: Any>(&self, name: &str) -> Option<&T> {
+        self.values.get(name).and_then(|&(_, ref x)| x.downcast_ref::<T>())
+    }
+}
+
+// The parsed values can't be compared, but they are derived from the raw
+// values, so those are compared instead.
+impl PartialEq for VendorCapabilities {
+    fn eq(&self, other: &VendorCapabilities) -> bool {
+        self.values.len() == other.values.len() &&
+            self.values.iter().zip(other.values.iter())
+                .all(|((name, &(ref value, _)), (other_name, &(ref other_value, _)))| {
+                    name == other_name && value == other_value
+                })
+    }
+}
+
+/// How user prompts are handled when a command other than one of the
+/// alert commands is run while a prompt is open.
+#[derive(Clone, Debug, PartialEq)]
+pub enum UnhandledPromptBehavior {
+    Accept,
+    AcceptAndNotify,
+    Dismiss,
+    DismissAndNotify,
+    Ignore,
+}
+
+impl UnhandledPromptBehavior {
+    pub fn from_json(body: &Json) -> WebDriverResult<UnhandledPromptBehavior> {
+        match try_opt!(body.as_string(),
+                       ErrorStatus::InvalidArgument,
+                       "'unhandledPromptBehavior' is not a string") {
+            "accept" => Ok(UnhandledPromptBehavior::Accept),
+            "accept and notify" => Ok(UnhandledPromptBehavior::AcceptAndNotify),
+            "dismiss" => Ok(UnhandledPromptBehavior::Dismiss),
+            "dismiss and notify" => Ok(UnhandledPromptBehavior::DismissAndNotify),
+            "ignore" => Ok(UnhandledPromptBehavior::Ignore),
+            x => Err(WebDriverError::new(ErrorStatus::InvalidArgument,
+                                         format!("Unknown unhandledPromptBehavior {}", x)))
+        }
+    }
+
+    pub fn from_capabilities(parameters: &NewSessionParameters)
+                             -> WebDriverResult<UnhandledPromptBehavior> {
+        match parameters.get("unhandledPromptBehavior") {
+            Some(x) => UnhandledPromptBehavior::from_json(x),
+            None => Ok(UnhandledPromptBehavior::default())
+        }
+    }
+}
+
+impl Default for UnhandledPromptBehavior {
+    fn default() -> UnhandledPromptBehavior {
+        UnhandledPromptBehavior::DismissAndNotify
+    }
+}
+
+impl ToJson for UnhandledPromptBehavior {
+    fn to_json(&self) -> Json {
+        Json::String(match *self {
+            UnhandledPromptBehavior::Accept => "accept",
+            UnhandledPromptBehavior::AcceptAndNotify => "accept and notify",
+            UnhandledPromptBehavior::Dismiss => "dismiss",
+            UnhandledPromptBehavior::DismissAndNotify => "dismiss and notify",
+            UnhandledPromptBehavior::Ignore => "ignore",
+        }.to_string())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ProxyType {
+    Pac,
+    Direct,
+    Autodetect,
+    System,
+    Manual,
+}
+
+impl ProxyType {
+    pub fn from_json(body: &Json) -> WebDriverResult<ProxyType> {
+        match try_opt!(body.as_string(),
+                       ErrorStatus::InvalidArgument,
+                       "'proxyType' is not a string") {
+            "pac" => Ok(ProxyType::Pac),
+            "direct" => Ok(ProxyType::Direct),
+            "autodetect" => Ok(ProxyType::Autodetect),
+            "system" => Ok(ProxyType::System),
+            "manual" => Ok(ProxyType::Manual),
+            x => Err(WebDriverError::new(ErrorStatus::InvalidArgument,
+                                         format!("Unknown proxyType {}", x)))
+        }
+    }
+}
+
+impl ToJson for ProxyType {
+    fn to_json(&self) -> Json {
+        Json::String(match *self {
+            ProxyType::Pac => "pac",
+            ProxyType::Direct => "direct",
+            ProxyType::Autodetect => "autodetect",
+            ProxyType::System => "system",
+            ProxyType::Manual => "manual",
+        }.to_string())
+    }
+}
+
+/// A proxy server given as `host[:port]`.
+///
+/// IPv6 hosts keep their surrounding brackets, so that the value
+/// serializes back to the same string it was parsed from.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProxyHost {
+    pub host: String,
+    pub port: Option<u16>,
+}
+
+impl ProxyHost {
+    pub fn parse(entry: &str, value: &str) -> WebDriverResult<ProxyHost> {
+        if value.contains("://") {
+            return Err(WebDriverError::new(
+                ErrorStatus::InvalidArgument,
+                format!("'{}' must not contain a scheme: {}", entry, value)));
+        }
+        if value.contains(|c| c == '/' || c == '@' || c == '?' || c == '#') {
+            return Err(WebDriverError::new(
+                ErrorStatus::InvalidArgument,
+                format!("'{}' was not of the form host[:port]: {}", entry, value)));
+        }
+
+        let (host, port) = if value.starts_with("[") {
+            let end = try_opt!(value.find(']'),
+                               ErrorStatus::InvalidArgument,
+                               format!("'{}' has an unterminated IPv6 address: {}", entry, value));
+            let rest = &value[end + 1..];
+            if rest.is_empty() {
+                (&value[..end + 1], None)
+            } else if rest.starts_with(":") {
+                (&value[..end + 1], Some(&rest[1..]))
+            } else {
+                return Err(WebDriverError::new(
+                    ErrorStatus::InvalidArgument,
+                    format!("'{}' was not of the form host[:port]: {}", entry, value)));
+            }
+        } else {
+            let mut parts = value.splitn(2, ':');
+            let host = parts.next().unwrap_or("");
+            (host, parts.next())
+        };
+
+        if host.is_empty() || host.contains(':') && !host.starts_with("[") {
+            return Err(WebDriverError::new(
+                ErrorStatus::InvalidArgument,
+                format!("'{}' was not of the form host[:port]: {}", entry, value)));
+        }
+
+        let port = match port {
+            Some(port) => Some(try_opt!(port.parse::<u16>().ok(),
+                                        ErrorStatus::InvalidArgument,
+                                        format!("'{}' has an invalid port: {}", entry, value))),
+            None => None,
+        };
+
+        Ok(ProxyHost {
+            host: host.to_string(),
+            port: port,
+        })
+    }
+}
+
+impl ToJson for ProxyHost {
+    fn to_json(&self) -> Json {
+        match self.port {
+            Some(port) => Json::String(format!("{}:{}", self.host, port)),
+            None => Json::String(self.host.clone()),
+        }
+    }
+}
+
+/// The `proxy` capability.
+///
+/// Keys that only apply to a particular `proxyType` are rejected when
+/// given with any other type.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProxyCapability {
+    pub proxy_type: ProxyType,
+    pub proxy_autoconfig_url: Option<String>,
+    pub ftp_proxy: Option<ProxyHost>,
+    pub http_proxy: Option<ProxyHost>,
+    pub ssl_proxy: Option<ProxyHost>,
+    pub socks_proxy: Option<ProxyHost>,
+    pub socks_version: Option<u8>,
+    pub no_proxy: Option<Vec<String>>,
+}
+
+impl ProxyCapability {
+    pub fn from_json(body: &Json) -> WebDriverResult<ProxyCapability> {
+        let data = try_opt!(body.as_object(),
+                            ErrorStatus::InvalidArgument,
+                            "'proxy' is not an object");
+
+        let proxy_type = try!(ProxyType::from_json(
+            try_opt!(data.get("proxyType"),
+                     ErrorStatus::InvalidArgument,
+                     "Missing 'proxyType' parameter")));
+
+        let mut proxy = ProxyCapability {
+            proxy_type: proxy_type,
+            proxy_autoconfig_url: None,
+            ftp_proxy: None,
+            http_proxy: None,
+            ssl_proxy: None,
+            socks_proxy: None,
+            socks_version: None,
+            no_proxy: None,
+        };
+
+        for (key, value) in data.iter() {
+            match &**key {
+                "proxyType" => {},
+                "proxyAutoconfigUrl" => {
+                    try!(proxy.require_type(key, ProxyType::Pac));
+                    let url = try_opt!(value.as_string(),
+                                       ErrorStatus::InvalidArgument,
+                                       "'proxyAutoconfigUrl' is not a string");
+                    if !url.contains("://") {
+                        return Err(WebDriverError::new(
+                            ErrorStatus::InvalidArgument,
+                            format!("'proxyAutoconfigUrl' is not a valid URL: {}", url)));
+                    }
+                    proxy.proxy_autoconfig_url = Some(url.to_string());
+                },
+                "ftpProxy" => {
+                    try!(proxy.require_type(key, ProxyType::Manual));
+                    proxy.ftp_proxy = Some(try!(ProxyCapability::parse_host(key, value)));
+                },
+                "httpProxy" => {
+                    try!(proxy.require_type(key, ProxyType::Manual));
+                    proxy.http_proxy = Some(try!(ProxyCapability::parse_host(key, value)));
+                },
+                "sslProxy" => {
+                    try!(proxy.require_type(key, ProxyType::Manual));
+                    proxy.ssl_proxy = Some(try!(ProxyCapability::parse_host(key, value)));
+                },
+                "socksProxy" => {
+                    try!(proxy.require_type(key, ProxyType::Manual));
+                    proxy.socks_proxy = Some(try!(ProxyCapability::parse_host(key, value)));
+                },
+                "socksVersion" => {
+                    try!(proxy.require_type(key, ProxyType::Manual));
+                    let version = try_opt!(value.as_u64(),
+                                           ErrorStatus::InvalidArgument,
+                                           "'socksVersion' is not a positive integer");
+                    if version > u8::max_value() as u64 {
+                        return Err(WebDriverError::new(
+                            ErrorStatus::InvalidArgument,
+                            format!("'socksVersion' out of range: {}", version)));
+                    }
+                    proxy.socks_version = Some(version as u8);
+                },
+                "noProxy" => {
+                    try!(proxy.require_type(key, ProxyType::Manual));
+                    let hosts = try_opt!(value.as_array(),
+                                         ErrorStatus::InvalidArgument,
+                                         "'noProxy' is not an array");
+                    let hosts = try!(hosts.iter().map(|x| {
+                        Ok(try_opt!(x.as_string(),
+                                    ErrorStatus::InvalidArgument,
+                                    "'noProxy' entry is not a string").to_string())
+                    }).collect::<WebDriverResult<Vec<String>>>());
+                    proxy.no_proxy = Some(hosts);
+                },
+                x if x.ends_with("Proxy") => {
+                    return Err(WebDriverError::new(
+                        ErrorStatus::InvalidArgument,
+                        format!("Proxy scheme is not supported: {}", x)))
+                },
+                x => {
+                    return Err(WebDriverError::new(
+                        ErrorStatus::InvalidArgument,
+                        format!("Unknown proxy parameter {}", x)))
+                }
+            }
+        }
+
+        if proxy.proxy_type == ProxyType::Pac && proxy.proxy_autoconfig_url.is_none() {
+            return Err(WebDriverError::new(
+                ErrorStatus::InvalidArgument,
+                "Missing 'proxyAutoconfigUrl' parameter for proxyType pac"));
+        }
+        if proxy.socks_proxy.is_some() && proxy.socks_version.is_none() {
+            return Err(WebDriverError::new(
+                ErrorStatus::InvalidArgument,
+                "Missing 'socksVersion' parameter for 'socksProxy'"));
+        }
+
+        Ok(proxy)
+    }
+
+    fn require_type(&self, key: &str, proxy_type: ProxyType) -> WebDriverResult<()> {
+        if self.proxy_type != proxy_type {
+            return Err(WebDriverError::new(
+                ErrorStatus::InvalidArgument,
+                format!("'{}' is only valid for proxyType {}",
+                        key, proxy_type.to_json())));
+        }
+        Ok(())
+    }
+
+    fn parse_host(key: &str, value: &Json) -> WebDriverResult<ProxyHost> {
+        let host = try_opt!(value.as_string(),
+                            ErrorStatus::InvalidArgument,
+                            format!("'{}' is not a string", key));
+        ProxyHost::parse(key, host)
+    }
+}
+
+impl ToJson for ProxyCapability {
+    fn to_json(&self) -> Json {
+        let mut data = BTreeMap::new();
+        data.insert("proxyType".to_string(), self.proxy_type.to_json());
+        if let Some(ref url) = self.proxy_autoconfig_url {
+            data.insert("proxyAutoconfigUrl".to_string(), url.to_json());
+        }
+        if let Some(ref host) = self.ftp_proxy {
+            data.insert("ftpProxy".to_string(), host.to_json());
+        }
+        if let Some(ref host) = self.http_proxy {
+            data.insert("httpProxy".to_string(), host.to_json());
+        }
+        if let Some(ref host) = self.ssl_proxy {
+            data.insert("sslProxy".to_string(), host.to_json());
+        }
+        if let Some(ref host) = self.socks_proxy {
+            data.insert("socksProxy".to_string(), host.to_json());
+        }
+        if let Some(version) = self.socks_version {
+            data.insert("socksVersion".to_string(), version.to_json());
+        }
+        if let Some(ref hosts) = self.no_proxy {
+            data.insert("noProxy".to_string(), hosts.to_json());
+        }
+        Json::Object(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use command::{NewSessionParameters, Parameters};
+    use error::{WebDriverError, ErrorStatus};
+    use rustc_serialize::json::{Json, ToJson};
+    use super::{CapabilitiesRegistry, ProxyCapability, ProxyHost, ProxyType};
+
+    fn parse(body: &str) -> ProxyCapability {
+        ProxyCapability::from_json(&Json::from_str(body).unwrap()).unwrap()
+    }
+
+    fn parse_err(body: &str) {
+        assert!(ProxyCapability::from_json(&Json::from_str(body).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_proxy_host() {
+        assert_eq!(ProxyHost::parse("httpProxy", "example.org:8080").unwrap(),
+                   ProxyHost { host: "example.org".into(), port: Some(8080) });
+        assert_eq!(ProxyHost::parse("httpProxy", "[::1]").unwrap(),
+                   ProxyHost { host: "[::1]".into(), port: None });
+        assert_eq!(ProxyHost::parse("httpProxy", "[::1]:3128").unwrap(),
+                   ProxyHost { host: "[::1]".into(), port: Some(3128) });
+        assert!(ProxyHost::parse("httpProxy", "http://example.org").is_err());
+        assert!(ProxyHost::parse("httpProxy", "example.org:99999").is_err());
+        assert!(ProxyHost::parse("httpProxy", "example.org/path").is_err());
+        assert!(ProxyHost::parse("httpProxy", ":8080").is_err());
+    }
+
+    #[test]
+    fn test_proxy_manual() {
+        let proxy = parse(r#"{"proxyType": "manual",
+                              "httpProxy": "example.org:8080",
+                              "socksProxy": "127.0.0.1:1080",
+                              "socksVersion": 5,
+                              "noProxy": ["localhost"]}"#);
+        assert_eq!(proxy.proxy_type, ProxyType::Manual);
+        assert_eq!(proxy.socks_version, Some(5));
+        assert_eq!(ProxyCapability::from_json(&proxy.to_json()).unwrap(), proxy);
+    }
+
+    #[test]
+    fn test_proxy_invalid() {
+        parse_err(r#"{"proxyType": "manual", "socksProxy": "127.0.0.1:1080"}"#);
+        parse_err(r#"{"proxyType": "manual", "httpsProxy": "example.org"}"#);
+        parse_err(r#"{"proxyType": "pac"}"#);
+        parse_err(r#"{"proxyType": "direct", "httpProxy": "example.org"}"#);
+        parse_err(r#"{"proxyType": "manual", "socksProxy": "a", "socksVersion": 256}"#);
+        parse_err(r#"{"proxyType": "unknown"}"#);
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Recording {
+        enabled: bool,
+    }
+
+    fn registry() -> CapabilitiesRegistry {
+        let mut registry = CapabilitiesRegistry::new();
+        registry.register("acme", |_, value| {
+            match value.find("enabled").and_then(|x| x.as_boolean()) {
+                Some(enabled) => Ok(Recording { enabled: enabled }),
+                None => Err(WebDriverError::new(ErrorStatus::InvalidArgument,
+                                                "'enabled' is not a boolean"))
+            }
+        });
+        registry
+    }
+
+    fn session_parameters(body: &str) -> NewSessionParameters {
+        NewSessionParameters::from_json(&Json::from_str(body).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_registry_vendor_capability() {
+        let mut parameters = session_parameters(
+            r#"{"desiredCapabilities": {"acme:recording": {"enabled": true},
+                                        "moz:firefoxOptions": {}}}"#);
+        registry().process(&mut parameters).unwrap();
+        assert_eq!(parameters.vendor.get::<Recording>("acme:recording"),
+                   Some(&Recording { enabled: true }));
+        assert!(!parameters.vendor.contains("moz:firefoxOptions"));
+    }
+
+    #[test]
+    fn test_registry_invalid() {
+        let mut parameters = session_parameters(
+            r#"{"requiredCapabilities": {"acme:recording": {"enabled": 1}}}"#);
+        assert!(registry().process(&mut parameters).is_err());
+
+        let mut parameters = session_parameters(
+            r#"{"desiredCapabilities": {"recording": true}}"#);
+        assert!(registry().process(&mut parameters).is_err());
+        let mut lenient = registry();
+        lenient.set_strict(false);
+        lenient.process(&mut parameters).unwrap();
+    }
+
+    #[test]
+    fn test_registry_default_strict() {
+        let mut parameters = session_parameters(
+            r#"{"desiredCapabilities": {"foo": 1}}"#);
+        let err = CapabilitiesRegistry::default().process(&mut parameters).err().unwrap();
+        assert_eq!(err.error, ErrorStatus::InvalidArgument);
+
+        let mut parameters = session_parameters(
+            r#"{"desiredCapabilities": {"browserName": "firefox"}}"#);
+        CapabilitiesRegistry::default().process(&mut parameters).unwrap();
+    }
+
+    #[test]
+    fn test_vendor_capabilities_eq() {
+        let process = |body: &str| {
+            let mut parameters = session_parameters(body);
+            registry().process(&mut parameters).unwrap();
+            parameters.vendor
+        };
+        let enabled = r#"{"desiredCapabilities": {"acme:recording": {"enabled": true}}}"#;
+        assert!(process(enabled) == process(enabled));
+        assert!(process(enabled) !=
+                process(r#"{"desiredCapabilities": {"acme:recording": {"enabled": false}}}"#));
+    }
+}
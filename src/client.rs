@@ -0,0 +1,207 @@
+use std::io::Read;
+
+use hyper::Client;
+use hyper::header::ContentType;
+use hyper::method::Method;
+use rustc_serialize::json::Json;
+
+use command::{WebDriverCommand, WebDriverExtensionCommand, WebDriverMessage};
+use error::{ErrorStatus, WebDriverResult, WebDriverError};
+use httpapi::{WebDriverHttpApi, WebDriverExtensionRoute, VoidWebDriverExtensionRoute};
+use response::WebDriverResponse;
+
+/// A client for a remote end speaking the WebDriver protocol over HTTP.
+///
+/// Commands are encoded with the same routes and parameter types the server
+/// uses to decode them. The client keeps track of the current session: it is
+/// set by a successful `NewSession` command and cleared by `DeleteSession`.
+pub struct WebDriverClient<U: WebDriverExtensionRoute=VoidWebDriverExtensionRoute> {
+    client: Client,
+    base_url: String,
+    api: WebDriverHttpApi<U>,
+    session_id: Option<String>,
+}
+
+impl <U: WebDriverExtensionRoute> WebDriverClient<U> {
+    /// Create a client for the remote end at `base_url`, e.g.
+    /// `http://127.0.0.1:4444`, which also serves `extension_routes`.
+    pub fn new(base_url: &str, extension_routes: &[(Method, &str, U)]) -> WebDriverClient<U> {
+        WebDriverClient {
+            client: Client::new(),
+            base_url: base_url.trim_right_matches('/').to_owned(),
+            api: WebDriverHttpApi::new(extension_routes),
+            session_id: None,
+        }
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    pub fn session_id(&self) -> Option<&str> {
+        self.session_id.as_ref().map(|x| &**x)
+    }
+
+    /// Use an existing session, e.g. one created by another client.
+    pub fn attach(&mut self, session_id: String) {
+        self.session_id = Some(session_id);
+    }
+
+    /// Send a command to the remote end, in the current session if there is
+    /// one, and wait for the response.
+    pub fn send(&mut self, command: WebDriverCommand<U::Command>)
+                -> WebDriverResult<WebDriverResponse> {
+        let session_id = match command {
+            WebDriverCommand::NewSession(_) | WebDriverCommand::Status => None,
+            _ => self.session_id.clone(),
+        };
+        let msg = WebDriverMessage::new(session_id, command);
+        let (method, path, body) = try!(self.api.encode_request(&msg));
+        let url = format!("{}{}", self.base_url, path);
+        debug!("Sending request {} {}", method, url);
+
+        let mut request = self.client.request(method, &*url)
+            .header(ContentType::json());
+        if let Some(ref body) = body {
+            request = request.body(&**body);
+        }
+        let mut resp = try!(request.send());
+
+        let mut resp_body = String::new();
+        try!(resp.read_to_string(&mut resp_body));
+        debug!("Got response {} {}", resp.status, resp_body);
+
+        let response = try!(decode_response(&msg.command, resp.status.to_u16(), &resp_body));
+        update_session(&mut self.session_id, &response);
+        Ok(response)
+    }
+}
+
+/// Decode the body of the response to `command`, which is an error unless
+/// the HTTP `status` was a success.
+///
+/// An error body that isn't a WebDriver error, such as the HTML page of a
+/// proxy, gives an `unknown error` carrying the HTTP status and the body.
+pub(crate) fn decode_response<T>(command: &WebDriverCommand<T>, status: u16, body: &str)
+                                 -> WebDriverResult<WebDriverResponse>
+    where T: WebDriverExtensionCommand
+{
+    if status < 200 || status >= 300 {
+        let err = Json::from_str(body)
+            .map_err(WebDriverError::from)
+            .and_then(|data| WebDriverError::from_json(&data));
+        return Err(match err {
+            Ok(err) => err,
+            Err(_) => WebDriverError::new(ErrorStatus::UnknownError,
+                                          format!("HTTP status {}: {}", status, body)),
+        });
+    }
+    let data = try!(Json::from_str(body));
+    WebDriverResponse::from_json(command, &data)
+}
+
+/// Track the session created or deleted by a command.
+pub(crate) fn update_session(session_id: &mut Option<String>, response: &WebDriverResponse) {
+    match *response {
+        WebDriverResponse::NewSession(ref x) => *session_id = Some(x.sessionId.clone()),
+        WebDriverResponse::DeleteSession => *session_id = None,
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use command::{GetParameters, LocatorParameters, Parameters, VoidWebDriverExtensionCommand,
+                  WebDriverCommand, WebDriverMessage};
+    use common::LocatorStrategy;
+    use error::{WebDriverResult, WebDriverError, ErrorStatus};
+    use httpapi::VoidWebDriverExtensionRoute;
+    use response::{NewSessionResponse, ValueResponse, WebDriverResponse};
+    use rustc_serialize::json::{Json, ToJson};
+    use server::{Session, TestServer, WebDriverHandler};
+    use super::{decode_response, WebDriverClient};
+
+    struct TestHandler {
+        url: Option<String>,
+    }
+
+    impl WebDriverHandler for TestHandler {
+        fn handle_command(&mut self, _: &Option<Session>,
+                          msg: WebDriverMessage) -> WebDriverResult<WebDriverResponse> {
+            match msg.command {
+                WebDriverCommand::NewSession(_) => {
+                    Ok(WebDriverResponse::NewSession(NewSessionResponse::new(
+                        "session-1".into(), Json::from_str(r#"{"browserName": "test"}"#).unwrap())))
+                },
+                WebDriverCommand::DeleteSession => Ok(WebDriverResponse::DeleteSession),
+                WebDriverCommand::Get(ref x) => {
+                    self.url = Some(x.url.clone());
+                    Ok(WebDriverResponse::Void)
+                },
+                WebDriverCommand::GetCurrentUrl => {
+                    Ok(WebDriverResponse::Generic(ValueResponse::new(self.url.to_json())))
+                },
+                _ => Err(WebDriverError::new(ErrorStatus::NoSuchElement, "Nothing here")),
+            }
+        }
+
+        fn delete_session(&mut self, _: &Option<Session>) {}
+    }
+
+    #[test]
+    fn test_client_session() {
+        let server = TestServer::start(TestHandler { url: None });
+        let mut client = WebDriverClient::<VoidWebDriverExtensionRoute>::new(&server.url(), &[]);
+
+        let parameters = Parameters::from_json(&Json::from_str("{}").unwrap()).unwrap();
+        match client.send(WebDriverCommand::NewSession(parameters)).unwrap() {
+            WebDriverResponse::NewSession(x) => {
+                assert_eq!(x.sessionId, "session-1");
+                assert_eq!(x.value.find("browserName"), Some(&"test".to_json()));
+            },
+            x => panic!("Unexpected response {:?}", x)
+        }
+        assert_eq!(client.session_id(), Some("session-1"));
+
+        let url = "https://example.org/".to_string();
+        client.send(WebDriverCommand::Get(GetParameters { url: url.clone() })).unwrap();
+        match client.send(WebDriverCommand::GetCurrentUrl).unwrap() {
+            WebDriverResponse::Generic(x) => assert_eq!(x.value, url.to_json()),
+            x => panic!("Unexpected response {:?}", x)
+        }
+
+        let locator = LocatorParameters {
+            using: LocatorStrategy::CSSSelector,
+            value: "a".into(),
+        };
+        let err = client.send(WebDriverCommand::FindElement(locator)).err().unwrap();
+        assert_eq!(err.error, ErrorStatus::NoSuchElement);
+        assert_eq!(err.message, "Nothing here");
+
+        client.send(WebDriverCommand::DeleteSession).unwrap();
+        assert_eq!(client.session_id(), None);
+
+        let err = client.send(WebDriverCommand::GetCurrentUrl).err().unwrap();
+        assert_eq!(err.error, ErrorStatus::InvalidSessionId);
+    }
+
+    #[test]
+    fn test_decode_response() {
+        let command = WebDriverCommand::<VoidWebDriverExtensionCommand>::GetTitle;
+
+        let err = decode_response(&command, 502, "<html>Bad Gateway</html>").err().unwrap();
+        assert_eq!(err.error, ErrorStatus::UnknownError);
+        assert_eq!(err.message, "HTTP status 502: <html>Bad Gateway</html>");
+
+        let body = r#"{"value": {"error": "no such window", "message": "closed"}}"#;
+        let err = decode_response(&command, 404, body).err().unwrap();
+        assert_eq!(err.error, ErrorStatus::NoSuchWindow);
+        assert_eq!(err.message, "closed");
+
+        match decode_response(&command, 200, r#"{"value": "Title"}"#).unwrap() {
+            WebDriverResponse::Generic(x) => assert_eq!(x.value, "Title".to_json()),
+            x => panic!("Unexpected response {:?}", x)
+        }
+        assert!(decode_response(&command, 200, "<html>").is_err());
+    }
+}
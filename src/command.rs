@@ -1,7 +1,10 @@
-use common::{Date, Nullable, WebElement, FrameId, LocatorStrategy};
+use capabilities::{ProxyCapability, VendorCapabilities};
+use common::{Date, Nullable, WebElement, ShadowRoot, FrameId, LocatorStrategy, WindowType};
 use error::{WebDriverResult, WebDriverError, ErrorStatus};
-use httpapi::{Route, WebDriverExtensionRoute, VoidWebDriverExtensionRoute};
+use httpapi::{decode_path_segment, Route, WebDriverExtensionRoute,
+              VoidWebDriverExtensionRoute};
 use regex::Captures;
+use rustc_serialize::base64::FromBase64;
 use rustc_serialize::json::{ToJson, Json};
 use std::collections::BTreeMap;
 use std::default::Default;
@@ -21,12 +24,16 @@ pub enum WebDriverCommand<T: WebDriverExtensionCommand> {
     GetWindowHandle,
     GetWindowHandles,
     CloseWindow,
+    NewWindow(NewWindowParameters),
     GetWindowSize,
     SetWindowSize(WindowSizeParameters),
     GetWindowPosition,
     SetWindowPosition(WindowPositionParameters),
+    GetWindowRect,
+    SetWindowRect(WindowRectParameters),
     MaximizeWindow,
-//    FullscreenWindow // Not supported in marionette
+    MinimizeWindow,
+    FullscreenWindow,
     SwitchToWindow(SwitchToWindowParameters),
     SwitchToFrame(SwitchToFrameParameters),
     SwitchToParentFrame,
@@ -35,10 +42,15 @@ pub enum WebDriverCommand<T: WebDriverExtensionCommand> {
     FindElementElement(WebElement, LocatorParameters),
     FindElementElements(WebElement, LocatorParameters),
     GetActiveElement,
+    GetShadowRoot(WebElement),
+    FindShadowRootElement(ShadowRoot, LocatorParameters),
+    FindShadowRootElements(ShadowRoot, LocatorParameters),
     IsDisplayed(WebElement),
     IsSelected(WebElement),
     GetElementAttribute(WebElement, String),
     GetElementProperty(WebElement, String),
+    GetComputedRole(WebElement),
+    GetComputedLabel(WebElement),
     GetCSSValue(WebElement, String),
     GetElementText(WebElement),
     GetElementTagName(WebElement),
@@ -65,6 +77,15 @@ pub enum WebDriverCommand<T: WebDriverExtensionCommand> {
     SendAlertText(SendKeysParameters),
     TakeScreenshot,
     TakeElementScreenshot(WebElement),
+    Print(PrintParameters),
+    SetPermission(SetPermissionParameters),
+    AddVirtualAuthenticator(AuthenticatorParameters),
+    RemoveVirtualAuthenticator(String),
+    AddCredential(String, CredentialParameters),
+    GetCredentials(String),
+    RemoveCredential(String, String),
+    RemoveAllCredentials(String),
+    SetUserVerified(String, UserVerificationParameters),
     Status,
     Extension(T)
 }
@@ -129,6 +150,10 @@ impl <U: WebDriverExtensionRoute> WebDriverMessage<U> {
             Route::GetWindowHandle => WebDriverCommand::GetWindowHandle,
             Route::GetWindowHandles => WebDriverCommand::GetWindowHandles,
             Route::CloseWindow => WebDriverCommand::CloseWindow,
+            Route::NewWindow => {
+                let parameters: NewWindowParameters = try!(Parameters::from_json(&body_data));
+                WebDriverCommand::NewWindow(parameters)
+            },
             Route::GetTimeouts => WebDriverCommand::GetTimeouts,
             Route::SetTimeouts => {
                 let parameters: TimeoutsParameters = try!(Parameters::from_json(&body_data));
@@ -144,7 +169,14 @@ impl <U: WebDriverExtensionRoute> WebDriverMessage<U> {
                 let parameters: WindowPositionParameters = try!(Parameters::from_json(&body_data));
                 WebDriverCommand::SetWindowPosition(parameters)
             },
+            Route::GetWindowRect => WebDriverCommand::GetWindowRect,
+            Route::SetWindowRect => {
+                let parameters: WindowRectParameters = try!(Parameters::from_json(&body_data));
+                WebDriverCommand::SetWindowRect(parameters)
+            },
             Route::MaximizeWindow => WebDriverCommand::MaximizeWindow,
+            Route::MinimizeWindow => WebDriverCommand::MinimizeWindow,
+            Route::FullscreenWindow => WebDriverCommand::FullscreenWindow,
             Route::SwitchToWindow => {
                 let parameters: SwitchToWindowParameters = try!(Parameters::from_json(&body_data));
                 WebDriverCommand::SwitchToWindow(parameters)
@@ -166,7 +198,7 @@ impl <U: WebDriverExtensionRoute> WebDriverMessage<U> {
                 let element_id = try_opt!(params.name("elementId"),
                                           ErrorStatus::InvalidArgument,
                                           "Missing elementId parameter");
-                let element = WebElement::new(element_id.as_str().into());
+                let element = WebElement::new(decode_path_segment(element_id.as_str()));
                 let parameters: LocatorParameters = try!(Parameters::from_json(&body_data));
                 WebDriverCommand::FindElementElement(element, parameters)
             },
@@ -174,109 +206,147 @@ impl <U: WebDriverExtensionRoute> WebDriverMessage<U> {
                 let element_id = try_opt!(params.name("elementId"),
                                           ErrorStatus::InvalidArgument,
                                           "Missing elementId parameter");
-                let element = WebElement::new(element_id.as_str().into());
+                let element = WebElement::new(decode_path_segment(element_id.as_str()));
                 let parameters: LocatorParameters = try!(Parameters::from_json(&body_data));
                 WebDriverCommand::FindElementElements(element, parameters)
             },
             Route::GetActiveElement => WebDriverCommand::GetActiveElement,
+            Route::GetShadowRoot => {
+                let element_id = try_opt!(params.name("elementId"),
+                                          ErrorStatus::InvalidArgument,
+                                          "Missing elementId parameter");
+                let element = WebElement::new(decode_path_segment(element_id.as_str()));
+                WebDriverCommand::GetShadowRoot(element)
+            },
+            Route::FindShadowRootElement => {
+                let shadow_id = try_opt!(params.name("shadowId"),
+                                         ErrorStatus::InvalidArgument,
+                                         "Missing shadowId parameter");
+                let shadow_root = ShadowRoot::new(decode_path_segment(shadow_id.as_str()));
+                let parameters: LocatorParameters = try!(Parameters::from_json(&body_data));
+                WebDriverCommand::FindShadowRootElement(shadow_root, parameters)
+            },
+            Route::FindShadowRootElements => {
+                let shadow_id = try_opt!(params.name("shadowId"),
+                                         ErrorStatus::InvalidArgument,
+                                         "Missing shadowId parameter");
+                let shadow_root = ShadowRoot::new(decode_path_segment(shadow_id.as_str()));
+                let parameters: LocatorParameters = try!(Parameters::from_json(&body_data));
+                WebDriverCommand::FindShadowRootElements(shadow_root, parameters)
+            },
             Route::IsDisplayed => {
                 let element_id = try_opt!(params.name("elementId"),
                                           ErrorStatus::InvalidArgument,
                                           "Missing elementId parameter");
-                let element = WebElement::new(element_id.as_str().into());
+                let element = WebElement::new(decode_path_segment(element_id.as_str()));
                 WebDriverCommand::IsDisplayed(element)
             },
             Route::IsSelected => {
                 let element_id = try_opt!(params.name("elementId"),
                                           ErrorStatus::InvalidArgument,
                                           "Missing elementId parameter");
-                let element = WebElement::new(element_id.as_str().into());
+                let element = WebElement::new(decode_path_segment(element_id.as_str()));
                 WebDriverCommand::IsSelected(element)
             },
             Route::GetElementAttribute => {
                 let element_id = try_opt!(params.name("elementId"),
                                           ErrorStatus::InvalidArgument,
                                           "Missing elementId parameter");
-                let element = WebElement::new(element_id.as_str().into());
+                let element = WebElement::new(decode_path_segment(element_id.as_str()));
                 let attr = try_opt!(params.name("name"),
                                     ErrorStatus::InvalidArgument,
-                                    "Missing name parameter").as_str();
-                WebDriverCommand::GetElementAttribute(element, attr.into())
+                                    "Missing name parameter");
+                WebDriverCommand::GetElementAttribute(element, decode_path_segment(attr.as_str()))
             },
             Route::GetElementProperty => {
                 let element_id = try_opt!(params.name("elementId"),
                                           ErrorStatus::InvalidArgument,
                                           "Missing elementId parameter");
-                let element = WebElement::new(element_id.as_str().into());
+                let element = WebElement::new(decode_path_segment(element_id.as_str()));
                 let property = try_opt!(params.name("name"),
                                         ErrorStatus::InvalidArgument,
-                                        "Missing name parameter").as_str();
-                WebDriverCommand::GetElementProperty(element, property.into())
+                                        "Missing name parameter");
+                WebDriverCommand::GetElementProperty(element,
+                                                     decode_path_segment(property.as_str()))
+            },
+            Route::GetComputedRole => {
+                let element_id = try_opt!(params.name("elementId"),
+                                          ErrorStatus::InvalidArgument,
+                                          "Missing elementId parameter");
+                let element = WebElement::new(decode_path_segment(element_id.as_str()));
+                WebDriverCommand::GetComputedRole(element)
+            },
+            Route::GetComputedLabel => {
+                let element_id = try_opt!(params.name("elementId"),
+                                          ErrorStatus::InvalidArgument,
+                                          "Missing elementId parameter");
+                let element = WebElement::new(decode_path_segment(element_id.as_str()));
+                WebDriverCommand::GetComputedLabel(element)
             },
             Route::GetCSSValue => {
                 let element_id = try_opt!(params.name("elementId"),
                                           ErrorStatus::InvalidArgument,
                                           "Missing elementId parameter");
-                let element = WebElement::new(element_id.as_str().into());
+                let element = WebElement::new(decode_path_segment(element_id.as_str()));
                 let property = try_opt!(params.name("propertyName"),
                                         ErrorStatus::InvalidArgument,
-                                        "Missing propertyName parameter").as_str();
-                WebDriverCommand::GetCSSValue(element, property.into())
+                                        "Missing propertyName parameter");
+                WebDriverCommand::GetCSSValue(element, decode_path_segment(property.as_str()))
             },
             Route::GetElementText => {
                 let element_id = try_opt!(params.name("elementId"),
                                           ErrorStatus::InvalidArgument,
                                           "Missing elementId parameter");
-                let element = WebElement::new(element_id.as_str().into());
+                let element = WebElement::new(decode_path_segment(element_id.as_str()));
                 WebDriverCommand::GetElementText(element)
             },
             Route::GetElementTagName => {
                 let element_id = try_opt!(params.name("elementId"),
                                           ErrorStatus::InvalidArgument,
                                           "Missing elementId parameter");
-                let element = WebElement::new(element_id.as_str().into());
+                let element = WebElement::new(decode_path_segment(element_id.as_str()));
                 WebDriverCommand::GetElementTagName(element)
             },
             Route::GetElementRect => {
                 let element_id = try_opt!(params.name("elementId"),
                                           ErrorStatus::InvalidArgument,
                                           "Missing elementId parameter");
-                let element = WebElement::new(element_id.as_str().into());
+                let element = WebElement::new(decode_path_segment(element_id.as_str()));
                 WebDriverCommand::GetElementRect(element)
             },
             Route::IsEnabled => {
                 let element_id = try_opt!(params.name("elementId"),
                                           ErrorStatus::InvalidArgument,
                                           "Missing elementId parameter");
-                let element = WebElement::new(element_id.as_str().into());
+                let element = WebElement::new(decode_path_segment(element_id.as_str()));
                 WebDriverCommand::IsEnabled(element)
             },
             Route::ElementClick => {
                 let element_id = try_opt!(params.name("elementId"),
                                           ErrorStatus::InvalidArgument,
                                           "Missing elementId parameter");
-                let element = WebElement::new(element_id.as_str().into());
+                let element = WebElement::new(decode_path_segment(element_id.as_str()));
                 WebDriverCommand::ElementClick(element)
             },
             Route::ElementTap => {
                 let element_id = try_opt!(params.name("elementId"),
                                           ErrorStatus::InvalidArgument,
                                           "Missing elementId parameter");
-                let element = WebElement::new(element_id.as_str().into());
+                let element = WebElement::new(decode_path_segment(element_id.as_str()));
                 WebDriverCommand::ElementTap(element)
             },
             Route::ElementClear => {
                 let element_id = try_opt!(params.name("elementId"),
                                           ErrorStatus::InvalidArgument,
                                           "Missing elementId parameter");
-                let element = WebElement::new(element_id.as_str().into());
+                let element = WebElement::new(decode_path_segment(element_id.as_str()));
                 WebDriverCommand::ElementClear(element)
             },
             Route::ElementSendKeys => {
                 let element_id = try_opt!(params.name("elementId"),
                                           ErrorStatus::InvalidArgument,
                                           "Missing elementId parameter");
-                let element = WebElement::new(element_id.as_str().into());
+                let element = WebElement::new(decode_path_segment(element_id.as_str()));
                 let parameters: SendKeysParameters = try!(Parameters::from_json(&body_data));
                 WebDriverCommand::ElementSendKeys(element, parameters)
             },
@@ -294,8 +364,8 @@ impl <U: WebDriverExtensionRoute> WebDriverMessage<U> {
             Route::GetNamedCookie => {
                 let name = try_opt!(params.name("name"),
                                     ErrorStatus::InvalidArgument,
-                                    "Missing 'name' parameter").as_str().into();
-                WebDriverCommand::GetNamedCookie(name)
+                                    "Missing 'name' parameter");
+                WebDriverCommand::GetNamedCookie(decode_path_segment(name.as_str()))
             },
             Route::AddCookie => {
                 let parameters: AddCookieParameters = try!(Parameters::from_json(&body_data));
@@ -307,8 +377,8 @@ impl <U: WebDriverExtensionRoute> WebDriverMessage<U> {
             Route::DeleteCookie => {
                 let name = try_opt!(params.name("name"),
                                     ErrorStatus::InvalidArgument,
-                                    "Missing name parameter").as_str().into();
-                WebDriverCommand::DeleteCookie(name)
+                                    "Missing name parameter");
+                WebDriverCommand::DeleteCookie(decode_path_segment(name.as_str()))
             },
             Route::PerformActions => {
                 let parameters: ActionsParameters = try!(Parameters::from_json(&body_data));
@@ -335,9 +405,69 @@ impl <U: WebDriverExtensionRoute> WebDriverMessage<U> {
                 let element_id = try_opt!(params.name("elementId"),
                                           ErrorStatus::InvalidArgument,
                                           "Missing elementId parameter");
-                let element = WebElement::new(element_id.as_str().into());
+                let element = WebElement::new(decode_path_segment(element_id.as_str()));
                 WebDriverCommand::TakeElementScreenshot(element)
             },
+            Route::Print => {
+                let parameters: PrintParameters = try!(Parameters::from_json(&body_data));
+                WebDriverCommand::Print(parameters)
+            },
+            Route::SetPermission => {
+                let parameters: SetPermissionParameters = try!(Parameters::from_json(&body_data));
+                WebDriverCommand::SetPermission(parameters)
+            },
+            Route::AddVirtualAuthenticator => {
+                let parameters: AuthenticatorParameters = try!(Parameters::from_json(&body_data));
+                WebDriverCommand::AddVirtualAuthenticator(parameters)
+            },
+            Route::RemoveVirtualAuthenticator => {
+                let authenticator_id = try_opt!(params.name("authenticatorId"),
+                                                ErrorStatus::InvalidArgument,
+                                                "Missing authenticatorId parameter");
+                let authenticator_id = decode_path_segment(authenticator_id.as_str());
+                WebDriverCommand::RemoveVirtualAuthenticator(authenticator_id)
+            },
+            Route::AddCredential => {
+                let authenticator_id = try_opt!(params.name("authenticatorId"),
+                                                ErrorStatus::InvalidArgument,
+                                                "Missing authenticatorId parameter");
+                let authenticator_id = decode_path_segment(authenticator_id.as_str());
+                let parameters: CredentialParameters = try!(Parameters::from_json(&body_data));
+                WebDriverCommand::AddCredential(authenticator_id, parameters)
+            },
+            Route::GetCredentials => {
+                let authenticator_id = try_opt!(params.name("authenticatorId"),
+                                                ErrorStatus::InvalidArgument,
+                                                "Missing authenticatorId parameter");
+                let authenticator_id = decode_path_segment(authenticator_id.as_str());
+                WebDriverCommand::GetCredentials(authenticator_id)
+            },
+            Route::RemoveCredential => {
+                let authenticator_id = try_opt!(params.name("authenticatorId"),
+                                                ErrorStatus::InvalidArgument,
+                                                "Missing authenticatorId parameter");
+                let authenticator_id = decode_path_segment(authenticator_id.as_str());
+                let credential_id = try_opt!(params.name("credentialId"),
+                                             ErrorStatus::InvalidArgument,
+                                             "Missing credentialId parameter");
+                WebDriverCommand::RemoveCredential(authenticator_id,
+                                                   decode_path_segment(credential_id.as_str()))
+            },
+            Route::RemoveAllCredentials => {
+                let authenticator_id = try_opt!(params.name("authenticatorId"),
+                                                ErrorStatus::InvalidArgument,
+                                                "Missing authenticatorId parameter");
+                let authenticator_id = decode_path_segment(authenticator_id.as_str());
+                WebDriverCommand::RemoveAllCredentials(authenticator_id)
+            },
+            Route::SetUserVerified => {
+                let authenticator_id = try_opt!(params.name("authenticatorId"),
+                                                ErrorStatus::InvalidArgument,
+                                                "Missing authenticatorId parameter");
+                let authenticator_id = decode_path_segment(authenticator_id.as_str());
+                let parameters: UserVerificationParameters = try!(Parameters::from_json(&body_data));
+                WebDriverCommand::SetUserVerified(authenticator_id, parameters)
+            },
             Route::Status => WebDriverCommand::Status,
             Route::Extension(ref extension) => {
                 try!(extension.command(params, &body_data))
@@ -347,7 +477,7 @@ impl <U: WebDriverExtensionRoute> WebDriverMessage<U> {
     }
 
     fn get_session_id(params: &Captures) -> Option<String> {
-        params.name("sessionId").map(|x| x.as_str().into())
+        params.name("sessionId").map(|x| decode_path_segment(x.as_str()))
     }
 }
 
@@ -368,6 +498,9 @@ impl <U:WebDriverExtensionRoute> ToJson for WebDriverMessage<U> {
             WebDriverCommand::GetAlertText |
             WebDriverCommand::GetNamedCookie(_) |
             WebDriverCommand::GetCookies |
+            WebDriverCommand::GetComputedLabel(_) |
+            WebDriverCommand::GetComputedRole(_) |
+            WebDriverCommand::GetCredentials(_) |
             WebDriverCommand::GetCSSValue(_, _) |
             WebDriverCommand::GetCurrentUrl |
             WebDriverCommand::GetElementAttribute(_, _) |
@@ -376,20 +509,27 @@ impl <U:WebDriverExtensionRoute> ToJson for WebDriverMessage<U> {
             WebDriverCommand::GetElementTagName(_) |
             WebDriverCommand::GetElementText(_) |
             WebDriverCommand::GetPageSource |
+            WebDriverCommand::GetShadowRoot(_) |
             WebDriverCommand::GetTimeouts |
             WebDriverCommand::GetTitle |
             WebDriverCommand::GetWindowHandle |
             WebDriverCommand::GetWindowHandles |
             WebDriverCommand::GetWindowPosition |
+            WebDriverCommand::GetWindowRect |
             WebDriverCommand::GetWindowSize |
             WebDriverCommand::GoBack |
             WebDriverCommand::GoForward |
             WebDriverCommand::IsDisplayed(_) |
             WebDriverCommand::IsEnabled(_) |
             WebDriverCommand::IsSelected(_) |
+            WebDriverCommand::FullscreenWindow |
             WebDriverCommand::MaximizeWindow |
+            WebDriverCommand::MinimizeWindow |
             WebDriverCommand::NewSession(_) |
             WebDriverCommand::Refresh |
+            WebDriverCommand::RemoveAllCredentials(_) |
+            WebDriverCommand::RemoveCredential(_, _) |
+            WebDriverCommand::RemoveVirtualAuthenticator(_) |
             WebDriverCommand::Status |
             WebDriverCommand::SwitchToParentFrame |
             WebDriverCommand::TakeElementScreenshot(_) |
@@ -398,6 +538,8 @@ impl <U:WebDriverExtensionRoute> ToJson for WebDriverMessage<U> {
             },
 
             WebDriverCommand::AddCookie(ref x) => Some(x.to_json()),
+            WebDriverCommand::AddCredential(_, ref x) => Some(x.to_json()),
+            WebDriverCommand::AddVirtualAuthenticator(ref x) => Some(x.to_json()),
             WebDriverCommand::ElementSendKeys(_, ref x) => Some(x.to_json()),
             WebDriverCommand::ExecuteAsyncScript(ref x) |
             WebDriverCommand::ExecuteScript(ref x) => Some(x.to_json()),
@@ -405,11 +547,18 @@ impl <U:WebDriverExtensionRoute> ToJson for WebDriverMessage<U> {
             WebDriverCommand::FindElementElements(_, ref x) => Some(x.to_json()),
             WebDriverCommand::FindElement(ref x) => Some(x.to_json()),
             WebDriverCommand::FindElements(ref x) => Some(x.to_json()),
+            WebDriverCommand::FindShadowRootElement(_, ref x) => Some(x.to_json()),
+            WebDriverCommand::FindShadowRootElements(_, ref x) => Some(x.to_json()),
             WebDriverCommand::Get(ref x) => Some(x.to_json()),
+            WebDriverCommand::NewWindow(ref x) => Some(x.to_json()),
             WebDriverCommand::PerformActions(ref x) => Some(x.to_json()),
+            WebDriverCommand::Print(ref x) => Some(x.to_json()),
             WebDriverCommand::SendAlertText(ref x) => Some(x.to_json()),
+            WebDriverCommand::SetPermission(ref x) => Some(x.to_json()),
             WebDriverCommand::SetTimeouts(ref x) => Some(x.to_json()),
+            WebDriverCommand::SetUserVerified(_, ref x) => Some(x.to_json()),
             WebDriverCommand::SetWindowPosition(ref x) => Some(x.to_json()),
+            WebDriverCommand::SetWindowRect(ref x) => Some(x.to_json()),
             WebDriverCommand::SetWindowSize(ref x) => Some(x.to_json()),
             WebDriverCommand::SwitchToFrame(ref x) => Some(x.to_json()),
             WebDriverCommand::SwitchToWindow(ref x) => Some(x.to_json()),
@@ -432,6 +581,8 @@ pub trait Parameters: Sized {
 pub struct NewSessionParameters {
     pub desired: BTreeMap<String, Json>,
     pub required: BTreeMap<String, Json>,
+    /// Vendor capabilities parsed by the server's `CapabilitiesRegistry`.
+    pub vendor: VendorCapabilities,
 }
 
 impl NewSessionParameters {
@@ -448,6 +599,13 @@ impl NewSessionParameters {
             desired
         }
     }
+
+    pub fn proxy(&self) -> WebDriverResult<Option<ProxyCapability>> {
+        match self.get("proxy") {
+            Some(x) => Ok(Some(try!(ProxyCapability::from_json(x)))),
+            None => Ok(None)
+        }
+    }
 }
 
 impl Parameters for NewSessionParameters {
@@ -474,9 +632,16 @@ impl Parameters for NewSessionParameters {
                 BTreeMap::new()
             };
 
+        for capabilities in [&desired_capabilities, &required_capabilities].iter() {
+            if let Some(proxy) = capabilities.get("proxy") {
+                try!(ProxyCapability::from_json(proxy));
+            }
+        }
+
         Ok(NewSessionParameters {
             desired: desired_capabilities,
-            required: required_capabilities
+            required: required_capabilities,
+            vendor: VendorCapabilities::default(),
         })
     }
 }
@@ -520,9 +685,10 @@ impl ToJson for GetParameters {
     }
 }
 
-#[derive(PartialEq)]
+#[derive(Clone, PartialEq)]
 pub struct TimeoutsParameters {
-    pub script: Option<u64>,
+    /// A `Null` script timeout means that scripts never time out.
+    pub script: Option<Nullable<u64>>,
     pub page_load: Option<u64>,
     pub implicit: Option<u64>,
 }
@@ -535,9 +701,11 @@ impl Parameters for TimeoutsParameters {
 
         let script = match data.get("script") {
             Some(json) => {
-                Some(try_opt!(json.as_u64(),
-                              ErrorStatus::InvalidArgument,
-                              "Script timeout duration was not a signed integer"))
+                Some(try!(Nullable::from_json(json, |x| {
+                    Ok(try_opt!(x.as_u64(),
+                                ErrorStatus::InvalidArgument,
+                                "Script timeout duration was not a positive integer"))
+                })))
             }
             None => None,
         };
@@ -546,16 +714,16 @@ impl Parameters for TimeoutsParameters {
             Some(json) => {
                 Some(try_opt!(json.as_u64(),
                               ErrorStatus::InvalidArgument,
-                              "Script timeout duration was not a signed integer"))
+                              "Page load timeout duration was not a positive integer"))
             }
             None => None,
         };
 
-        let implicit = match data.get("script") {
+        let implicit = match data.get("implicit") {
             Some(json) => {
                 Some(try_opt!(json.as_u64(),
                               ErrorStatus::InvalidArgument,
-                              "Script timeout duration was not a signed integer"))
+                              "Implicit timeout duration was not a positive integer"))
             }
             None => None,
         };
@@ -571,7 +739,7 @@ impl Parameters for TimeoutsParameters {
 impl ToJson for TimeoutsParameters {
     fn to_json(&self) -> Json {
         let mut data = BTreeMap::new();
-        if let Some(ms) = self.script {
+        if let Some(ref ms) = self.script {
             data.insert("script".into(), ms.to_json());
         }
         if let Some(ms) = self.page_load {
@@ -654,6 +822,109 @@ impl ToJson for WindowPositionParameters {
     }
 }
 
+/// Parameters for `SetWindowRect`. A `Null` value leaves that part of the
+/// window rect unchanged.
+#[derive(PartialEq)]
+pub struct WindowRectParameters {
+    pub x: Nullable<i64>,
+    pub y: Nullable<i64>,
+    pub width: Nullable<u64>,
+    pub height: Nullable<u64>,
+}
+
+impl WindowRectParameters {
+    fn parse_position(data: &BTreeMap<String, Json>, name: &str) -> WebDriverResult<Nullable<i64>> {
+        match data.get(name) {
+            Some(json) => Nullable::from_json(json, |x| {
+                let value = try_opt!(x.as_i64(),
+                                     ErrorStatus::InvalidArgument,
+                                     format!("'{}' is not an integer", name));
+                if value < i32::min_value() as i64 || value > i32::max_value() as i64 {
+                    return Err(WebDriverError::new(ErrorStatus::InvalidArgument,
+                                                   format!("'{}' is out of range", name)));
+                }
+                Ok(value)
+            }),
+            None => Ok(Nullable::Null)
+        }
+    }
+
+    fn parse_dimension(data: &BTreeMap<String, Json>, name: &str) -> WebDriverResult<Nullable<u64>> {
+        match data.get(name) {
+            Some(json) => Nullable::from_json(json, |x| {
+                let value = try_opt!(x.as_u64(),
+                                     ErrorStatus::InvalidArgument,
+                                     format!("'{}' is not a positive integer", name));
+                if value > i32::max_value() as u64 {
+                    return Err(WebDriverError::new(ErrorStatus::InvalidArgument,
+                                                   format!("'{}' is out of range", name)));
+                }
+                Ok(value)
+            }),
+            None => Ok(Nullable::Null)
+        }
+    }
+}
+
+impl Parameters for WindowRectParameters {
+    fn from_json(body: &Json) -> WebDriverResult<WindowRectParameters> {
+        let data = try_opt!(body.as_object(),
+                            ErrorStatus::InvalidArgument,
+                            "Message body was not an object");
+        Ok(WindowRectParameters {
+            x: try!(WindowRectParameters::parse_position(data, "x")),
+            y: try!(WindowRectParameters::parse_position(data, "y")),
+            width: try!(WindowRectParameters::parse_dimension(data, "width")),
+            height: try!(WindowRectParameters::parse_dimension(data, "height")),
+        })
+    }
+}
+
+impl ToJson for WindowRectParameters {
+    fn to_json(&self) -> Json {
+        let mut data = BTreeMap::new();
+        data.insert("x".to_string(), self.x.to_json());
+        data.insert("y".to_string(), self.y.to_json());
+        data.insert("width".to_string(), self.width.to_json());
+        data.insert("height".to_string(), self.height.to_json());
+        Json::Object(data)
+    }
+}
+
+#[derive(PartialEq)]
+pub struct NewWindowParameters {
+    /// The kind of window the client would prefer. Remote ends are free to
+    /// create the other kind, and unrecognised hints are ignored.
+    pub type_hint: Option<WindowType>,
+}
+
+impl Parameters for NewWindowParameters {
+    fn from_json(body: &Json) -> WebDriverResult<NewWindowParameters> {
+        let data = try_opt!(body.as_object(),
+                            ErrorStatus::InvalidArgument,
+                            "Message body was not an object");
+        let type_hint = match data.get("type") {
+            Some(&Json::Null) | None => None,
+            Some(json @ &Json::String(_)) => WindowType::from_json(json).ok(),
+            Some(_) => return Err(WebDriverError::new(ErrorStatus::InvalidArgument,
+                                                      "'type' is not a string")),
+        };
+        Ok(NewWindowParameters {
+            type_hint: type_hint
+        })
+    }
+}
+
+impl ToJson for NewWindowParameters {
+    fn to_json(&self) -> Json {
+        let mut data = BTreeMap::new();
+        if let Some(ref type_hint) = self.type_hint {
+            data.insert("type".to_string(), type_hint.to_json());
+        }
+        Json::Object(data)
+    }
+}
+
 #[derive(PartialEq)]
 pub struct SwitchToWindowParameters {
     pub handle: String
@@ -683,7 +954,7 @@ impl ToJson for SwitchToWindowParameters {
     }
 }
 
-#[derive(PartialEq)]
+#[derive(Clone, PartialEq)]
 pub struct LocatorParameters {
     pub using: LocatorStrategy,
     pub value: String
@@ -1022,7 +1293,673 @@ impl ToJson for TakeScreenshotParameters {
     }
 }
 
-#[derive(PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
+pub enum PrintOrientation {
+    Landscape,
+    Portrait,
+}
+
+impl Parameters for PrintOrientation {
+    fn from_json(body: &Json) -> WebDriverResult<PrintOrientation> {
+        match body.as_string() {
+            Some("landscape") => Ok(PrintOrientation::Landscape),
+            Some("portrait") => Ok(PrintOrientation::Portrait),
+            Some(x) => Err(WebDriverError::new(ErrorStatus::InvalidArgument,
+                                               format!("Unknown orientation {}", x))),
+            None => Err(WebDriverError::new(ErrorStatus::InvalidArgument,
+                                            "Parameter 'orientation' was not a string"))
+        }
+    }
+}
+
+impl ToJson for PrintOrientation {
+    fn to_json(&self) -> Json {
+        match *self {
+            PrintOrientation::Landscape => "landscape",
+            PrintOrientation::Portrait => "portrait",
+        }.to_json()
+    }
+}
+
+impl Default for PrintOrientation {
+    fn default() -> PrintOrientation {
+        PrintOrientation::Portrait
+    }
+}
+
+/// Page size in centimetres.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PrintPage {
+    pub width: f64,
+    pub height: f64,
+}
+
+/// The smallest page dimension allowed, one point, in centimetres.
+const MIN_PAGE_SIZE: f64 = 2.54 / 72.0;
+
+impl Parameters for PrintPage {
+    fn from_json(body: &Json) -> WebDriverResult<PrintPage> {
+        let data = try_opt!(body.as_object(),
+                            ErrorStatus::InvalidArgument,
+                            "Parameter 'page' was not an object");
+        let default = PrintPage::default();
+        Ok(PrintPage {
+            width: try!(parse_print_number(data, "width", MIN_PAGE_SIZE, default.width)),
+            height: try!(parse_print_number(data, "height", MIN_PAGE_SIZE, default.height)),
+        })
+    }
+}
+
+impl ToJson for PrintPage {
+    fn to_json(&self) -> Json {
+        let mut data = BTreeMap::new();
+        data.insert("width".to_owned(), self.width.to_json());
+        data.insert("height".to_owned(), self.height.to_json());
+        Json::Object(data)
+    }
+}
+
+impl Default for PrintPage {
+    fn default() -> PrintPage {
+        PrintPage {
+            width: 21.59,
+            height: 27.94,
+        }
+    }
+}
+
+/// Page margins in centimetres.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PrintMargins {
+    pub top: f64,
+    pub bottom: f64,
+    pub left: f64,
+    pub right: f64,
+}
+
+impl Parameters for PrintMargins {
+    fn from_json(body: &Json) -> WebDriverResult<PrintMargins> {
+        let data = try_opt!(body.as_object(),
+                            ErrorStatus::InvalidArgument,
+                            "Parameter 'margin' was not an object");
+        Ok(PrintMargins {
+            top: try!(parse_print_number(data, "top", 0.0, 1.0)),
+            bottom: try!(parse_print_number(data, "bottom", 0.0, 1.0)),
+            left: try!(parse_print_number(data, "left", 0.0, 1.0)),
+            right: try!(parse_print_number(data, "right", 0.0, 1.0)),
+        })
+    }
+}
+
+impl ToJson for PrintMargins {
+    fn to_json(&self) -> Json {
+        let mut data = BTreeMap::new();
+        data.insert("top".to_owned(), self.top.to_json());
+        data.insert("bottom".to_owned(), self.bottom.to_json());
+        data.insert("left".to_owned(), self.left.to_json());
+        data.insert("right".to_owned(), self.right.to_json());
+        Json::Object(data)
+    }
+}
+
+impl Default for PrintMargins {
+    fn default() -> PrintMargins {
+        PrintMargins {
+            top: 1.0,
+            bottom: 1.0,
+            left: 1.0,
+            right: 1.0,
+        }
+    }
+}
+
+fn parse_print_number(data: &BTreeMap<String, Json>, name: &str, min: f64, default: f64)
+                      -> WebDriverResult<f64> {
+    match data.get(name) {
+        Some(x) => {
+            let value = try_opt!(x.as_f64(),
+                                 ErrorStatus::InvalidArgument,
+                                 format!("Parameter '{}' was not a number", name));
+            if value < min {
+                return Err(WebDriverError::new(
+                    ErrorStatus::InvalidArgument,
+                    format!("Parameter '{}' must be at least {}", name, min)));
+            }
+            Ok(value)
+        },
+        None => Ok(default)
+    }
+}
+
+/// A range of pages to print, counted from 1. An `end` of `None` extends
+/// the range to the last page.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PageRange {
+    pub start: u64,
+    pub end: Option<u64>,
+}
+
+impl PageRange {
+    fn parse_page(value: &str) -> WebDriverResult<u64> {
+        match value.trim().parse::<u64>() {
+            Ok(page) if page > 0 => Ok(page),
+            _ => Err(WebDriverError::new(ErrorStatus::InvalidArgument,
+                                         format!("Invalid page number {}", value)))
+        }
+    }
+}
+
+impl Parameters for PageRange {
+    fn from_json(body: &Json) -> WebDriverResult<PageRange> {
+        if let Some(page) = body.as_u64() {
+            if page == 0 {
+                return Err(WebDriverError::new(ErrorStatus::InvalidArgument,
+                                               "Page numbers start at 1"));
+            }
+            return Ok(PageRange {
+                start: page,
+                end: Some(page),
+            });
+        }
+
+        let value = try_opt!(body.as_string(),
+                             ErrorStatus::InvalidArgument,
+                             "Page range was not a string or a positive integer");
+        let parts = value.split('-').collect::<Vec<&str>>();
+        let (start, end) = match parts.len() {
+            1 => {
+                let page = try!(PageRange::parse_page(parts[0]));
+                (page, Some(page))
+            },
+            2 => {
+                let start = if parts[0].trim().is_empty() {
+                    1
+                } else {
+                    try!(PageRange::parse_page(parts[0]))
+                };
+                let end = if parts[1].trim().is_empty() {
+                    None
+                } else {
+                    Some(try!(PageRange::parse_page(parts[1])))
+                };
+                (start, end)
+            },
+            _ => return Err(WebDriverError::new(ErrorStatus::InvalidArgument,
+                                                format!("Invalid page range {}", value)))
+        };
+
+        if end.map_or(false, |end| start > end) {
+            return Err(WebDriverError::new(ErrorStatus::InvalidArgument,
+                                           format!("Page range {} ends before it starts", value)));
+        }
+
+        Ok(PageRange {
+            start: start,
+            end: end,
+        })
+    }
+}
+
+impl ToJson for PageRange {
+    fn to_json(&self) -> Json {
+        match self.end {
+            Some(end) if end == self.start => format!("{}", end),
+            Some(end) => format!("{}-{}", self.start, end),
+            None => format!("{}-", self.start),
+        }.to_json()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PrintParameters {
+    pub orientation: PrintOrientation,
+    pub scale: f64,
+    pub background: bool,
+    pub page: PrintPage,
+    pub margin: PrintMargins,
+    pub shrink_to_fit: bool,
+    pub page_ranges: Vec<PageRange>,
+}
+
+impl Parameters for PrintParameters {
+    fn from_json(body: &Json) -> WebDriverResult<PrintParameters> {
+        let data = try_opt!(body.as_object(),
+                            ErrorStatus::InvalidArgument,
+                            "Message body was not an object");
+
+        let orientation = match data.get("orientation") {
+            Some(x) => try!(PrintOrientation::from_json(x)),
+            None => PrintOrientation::default()
+        };
+
+        let scale = try!(parse_print_number(data, "scale", 0.1, 1.0));
+        if scale > 2.0 {
+            return Err(WebDriverError::new(ErrorStatus::InvalidArgument,
+                                           "Parameter 'scale' must be at most 2"));
+        }
+
+        let background = match data.get("background") {
+            Some(x) => try_opt!(x.as_boolean(),
+                                ErrorStatus::InvalidArgument,
+                                "Parameter 'background' was not a boolean"),
+            None => false
+        };
+
+        let page = match data.get("page") {
+            Some(x) => try!(PrintPage::from_json(x)),
+            None => PrintPage::default()
+        };
+
+        let margin = match data.get("margin") {
+            Some(x) => try!(PrintMargins::from_json(x)),
+            None => PrintMargins::default()
+        };
+
+        if margin.left + margin.right >= page.width {
+            return Err(WebDriverError::new(ErrorStatus::InvalidArgument,
+                                           "Left and right margins must fit within the page width"));
+        }
+        if margin.top + margin.bottom >= page.height {
+            return Err(WebDriverError::new(ErrorStatus::InvalidArgument,
+                                           "Top and bottom margins must fit within the page height"));
+        }
+
+        let shrink_to_fit = match data.get("shrinkToFit") {
+            Some(x) => try_opt!(x.as_boolean(),
+                                ErrorStatus::InvalidArgument,
+                                "Parameter 'shrinkToFit' was not a boolean"),
+            None => true
+        };
+
+        let page_ranges = match data.get("pageRanges") {
+            Some(x) => {
+                let ranges = try_opt!(x.as_array(),
+                                      ErrorStatus::InvalidArgument,
+                                      "Parameter 'pageRanges' was not an array");
+                try!(ranges.iter().map(PageRange::from_json).collect::<WebDriverResult<Vec<_>>>())
+            },
+            None => vec![]
+        };
+
+        Ok(PrintParameters {
+            orientation: orientation,
+            scale: scale,
+            background: background,
+            page: page,
+            margin: margin,
+            shrink_to_fit: shrink_to_fit,
+            page_ranges: page_ranges,
+        })
+    }
+}
+
+impl ToJson for PrintParameters {
+    fn to_json(&self) -> Json {
+        let mut data = BTreeMap::new();
+        data.insert("orientation".to_owned(), self.orientation.to_json());
+        data.insert("scale".to_owned(), self.scale.to_json());
+        data.insert("background".to_owned(), self.background.to_json());
+        data.insert("page".to_owned(), self.page.to_json());
+        data.insert("margin".to_owned(), self.margin.to_json());
+        data.insert("shrinkToFit".to_owned(), self.shrink_to_fit.to_json());
+        data.insert("pageRanges".to_owned(),
+                    self.page_ranges.iter().map(|x| x.to_json()).collect::<Vec<Json>>().to_json());
+        Json::Object(data)
+    }
+}
+
+/// A permission descriptor; `name` identifies the permission and any other
+/// members are permission specific, e.g. `userVisibleOnly` for `push`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PermissionDescriptor {
+    pub name: String,
+    pub extra: BTreeMap<String, Json>,
+}
+
+impl Parameters for PermissionDescriptor {
+    fn from_json(body: &Json) -> WebDriverResult<PermissionDescriptor> {
+        let data = try_opt!(body.as_object(),
+                            ErrorStatus::InvalidArgument,
+                            "Parameter 'descriptor' was not an object");
+        let name = try_opt!(
+            try_opt!(data.get("name"),
+                     ErrorStatus::InvalidArgument,
+                     "Missing 'name' parameter").as_string(),
+            ErrorStatus::InvalidArgument,
+            "Parameter 'name' was not a string").to_owned();
+        let extra = data.iter()
+            .filter(|&(key, _)| key != "name")
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        Ok(PermissionDescriptor {
+            name: name,
+            extra: extra,
+        })
+    }
+}
+
+impl ToJson for PermissionDescriptor {
+    fn to_json(&self) -> Json {
+        let mut data = self.extra.clone();
+        data.insert("name".to_owned(), self.name.to_json());
+        Json::Object(data)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum PermissionState {
+    Granted,
+    Denied,
+    Prompt,
+}
+
+impl Parameters for PermissionState {
+    fn from_json(body: &Json) -> WebDriverResult<PermissionState> {
+        match body.as_string() {
+            Some("granted") => Ok(PermissionState::Granted),
+            Some("denied") => Ok(PermissionState::Denied),
+            Some("prompt") => Ok(PermissionState::Prompt),
+            Some(x) => Err(WebDriverError::new(ErrorStatus::InvalidArgument,
+                                               format!("Unknown permission state {}", x))),
+            None => Err(WebDriverError::new(ErrorStatus::InvalidArgument,
+                                            "Parameter 'state' was not a string"))
+        }
+    }
+}
+
+impl ToJson for PermissionState {
+    fn to_json(&self) -> Json {
+        match *self {
+            PermissionState::Granted => "granted",
+            PermissionState::Denied => "denied",
+            PermissionState::Prompt => "prompt",
+        }.to_json()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SetPermissionParameters {
+    pub descriptor: PermissionDescriptor,
+    pub state: PermissionState,
+}
+
+impl Parameters for SetPermissionParameters {
+    fn from_json(body: &Json) -> WebDriverResult<SetPermissionParameters> {
+        let data = try_opt!(body.as_object(),
+                            ErrorStatus::InvalidArgument,
+                            "Message body was not an object");
+        let descriptor = try!(PermissionDescriptor::from_json(
+            try_opt!(data.get("descriptor"),
+                     ErrorStatus::InvalidArgument,
+                     "Missing 'descriptor' parameter")));
+        let state = try!(PermissionState::from_json(
+            try_opt!(data.get("state"),
+                     ErrorStatus::InvalidArgument,
+                     "Missing 'state' parameter")));
+        Ok(SetPermissionParameters {
+            descriptor: descriptor,
+            state: state,
+        })
+    }
+}
+
+impl ToJson for SetPermissionParameters {
+    fn to_json(&self) -> Json {
+        let mut data = BTreeMap::new();
+        data.insert("descriptor".to_owned(), self.descriptor.to_json());
+        data.insert("state".to_owned(), self.state.to_json());
+        Json::Object(data)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum AuthenticatorProtocol {
+    Ctap1U2f,
+    Ctap2,
+    Ctap2_1,
+}
+
+impl Parameters for AuthenticatorProtocol {
+    fn from_json(body: &Json) -> WebDriverResult<AuthenticatorProtocol> {
+        match body.as_string() {
+            Some("ctap1/u2f") => Ok(AuthenticatorProtocol::Ctap1U2f),
+            Some("ctap2") => Ok(AuthenticatorProtocol::Ctap2),
+            Some("ctap2_1") => Ok(AuthenticatorProtocol::Ctap2_1),
+            Some(x) => Err(WebDriverError::new(ErrorStatus::InvalidArgument,
+                                               format!("Unknown authenticator protocol {}", x))),
+            None => Err(WebDriverError::new(ErrorStatus::InvalidArgument,
+                                            "Parameter 'protocol' was not a string"))
+        }
+    }
+}
+
+impl ToJson for AuthenticatorProtocol {
+    fn to_json(&self) -> Json {
+        match *self {
+            AuthenticatorProtocol::Ctap1U2f => "ctap1/u2f",
+            AuthenticatorProtocol::Ctap2 => "ctap2",
+            AuthenticatorProtocol::Ctap2_1 => "ctap2_1",
+        }.to_json()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum AuthenticatorTransport {
+    Usb,
+    Nfc,
+    Ble,
+    SmartCard,
+    Hybrid,
+    Internal,
+}
+
+impl Parameters for AuthenticatorTransport {
+    fn from_json(body: &Json) -> WebDriverResult<AuthenticatorTransport> {
+        match body.as_string() {
+            Some("usb") => Ok(AuthenticatorTransport::Usb),
+            Some("nfc") => Ok(AuthenticatorTransport::Nfc),
+            Some("ble") => Ok(AuthenticatorTransport::Ble),
+            Some("smart-card") => Ok(AuthenticatorTransport::SmartCard),
+            Some("hybrid") => Ok(AuthenticatorTransport::Hybrid),
+            Some("internal") => Ok(AuthenticatorTransport::Internal),
+            Some(x) => Err(WebDriverError::new(ErrorStatus::InvalidArgument,
+                                               format!("Unknown authenticator transport {}", x))),
+            None => Err(WebDriverError::new(ErrorStatus::InvalidArgument,
+                                            "Parameter 'transport' was not a string"))
+        }
+    }
+}
+
+impl ToJson for AuthenticatorTransport {
+    fn to_json(&self) -> Json {
+        match *self {
+            AuthenticatorTransport::Usb => "usb",
+            AuthenticatorTransport::Nfc => "nfc",
+            AuthenticatorTransport::Ble => "ble",
+            AuthenticatorTransport::SmartCard => "smart-card",
+            AuthenticatorTransport::Hybrid => "hybrid",
+            AuthenticatorTransport::Internal => "internal",
+        }.to_json()
+    }
+}
+
+fn parse_optional_bool(data: &BTreeMap<String, Json>, name: &str, default: bool)
+                       -> WebDriverResult<bool> {
+    match data.get(name) {
+        Some(x) => Ok(try_opt!(x.as_boolean(),
+                               ErrorStatus::InvalidArgument,
+                               format!("Parameter '{}' was not a boolean", name))),
+        None => Ok(default)
+    }
+}
+
+/// Parameters for creating a virtual WebAuthn authenticator.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AuthenticatorParameters {
+    pub protocol: AuthenticatorProtocol,
+    pub transport: AuthenticatorTransport,
+    pub has_resident_key: bool,
+    pub has_user_verification: bool,
+    pub is_user_consenting: bool,
+    pub is_user_verified: bool,
+}
+
+impl Parameters for AuthenticatorParameters {
+    fn from_json(body: &Json) -> WebDriverResult<AuthenticatorParameters> {
+        let data = try_opt!(body.as_object(),
+                            ErrorStatus::InvalidArgument,
+                            "Message body was not an object");
+
+        let protocol = try!(AuthenticatorProtocol::from_json(
+            try_opt!(data.get("protocol"),
+                     ErrorStatus::InvalidArgument,
+                     "Missing 'protocol' parameter")));
+        let transport = try!(AuthenticatorTransport::from_json(
+            try_opt!(data.get("transport"),
+                     ErrorStatus::InvalidArgument,
+                     "Missing 'transport' parameter")));
+
+        Ok(AuthenticatorParameters {
+            protocol: protocol,
+            transport: transport,
+            has_resident_key: try!(parse_optional_bool(data, "hasResidentKey", false)),
+            has_user_verification: try!(parse_optional_bool(data, "hasUserVerification", false)),
+            is_user_consenting: try!(parse_optional_bool(data, "isUserConsenting", true)),
+            is_user_verified: try!(parse_optional_bool(data, "isUserVerified", false)),
+        })
+    }
+}
+
+impl ToJson for AuthenticatorParameters {
+    fn to_json(&self) -> Json {
+        let mut data = BTreeMap::new();
+        data.insert("protocol".to_owned(), self.protocol.to_json());
+        data.insert("transport".to_owned(), self.transport.to_json());
+        data.insert("hasResidentKey".to_owned(), self.has_resident_key.to_json());
+        data.insert("hasUserVerification".to_owned(), self.has_user_verification.to_json());
+        data.insert("isUserConsenting".to_owned(), self.is_user_consenting.to_json());
+        data.insert("isUserVerified".to_owned(), self.is_user_verified.to_json());
+        Json::Object(data)
+    }
+}
+
+/// Check that a parameter is unpadded base64url, as used for WebAuthn
+/// binary values, and return it unchanged.
+fn parse_base64url(data: &BTreeMap<String, Json>, name: &str) -> WebDriverResult<String> {
+    let value = try_opt!(try_opt!(data.get(name),
+                                  ErrorStatus::InvalidArgument,
+                                  format!("Missing '{}' parameter", name)).as_string(),
+                         ErrorStatus::InvalidArgument,
+                         format!("Parameter '{}' was not a string", name));
+    let valid_chars = value.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid_chars || value.len() % 4 == 1 || value.from_base64().is_err() {
+        return Err(WebDriverError::new(ErrorStatus::InvalidArgument,
+                                       format!("Parameter '{}' was not base64url encoded", name)));
+    }
+    Ok(value.to_owned())
+}
+
+/// A WebAuthn credential to add to a virtual authenticator. Binary values
+/// are kept in their base64url encoded form.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CredentialParameters {
+    pub credential_id: String,
+    pub is_resident_credential: bool,
+    pub rp_id: String,
+    pub private_key: String,
+    pub user_handle: Option<String>,
+    pub sign_count: u64,
+}
+
+impl Parameters for CredentialParameters {
+    fn from_json(body: &Json) -> WebDriverResult<CredentialParameters> {
+        let data = try_opt!(body.as_object(),
+                            ErrorStatus::InvalidArgument,
+                            "Message body was not an object");
+
+        let is_resident_credential = try_opt!(
+            try_opt!(data.get("isResidentCredential"),
+                     ErrorStatus::InvalidArgument,
+                     "Missing 'isResidentCredential' parameter").as_boolean(),
+            ErrorStatus::InvalidArgument,
+            "Parameter 'isResidentCredential' was not a boolean");
+
+        let rp_id = try_opt!(
+            try_opt!(data.get("rpId"),
+                     ErrorStatus::InvalidArgument,
+                     "Missing 'rpId' parameter").as_string(),
+            ErrorStatus::InvalidArgument,
+            "Parameter 'rpId' was not a string").to_owned();
+
+        let user_handle = match data.get("userHandle") {
+            Some(&Json::Null) | None => None,
+            Some(_) => Some(try!(parse_base64url(data, "userHandle")))
+        };
+
+        let sign_count = try_opt!(
+            try_opt!(data.get("signCount"),
+                     ErrorStatus::InvalidArgument,
+                     "Missing 'signCount' parameter").as_u64(),
+            ErrorStatus::InvalidArgument,
+            "Parameter 'signCount' was not a positive integer");
+
+        Ok(CredentialParameters {
+            credential_id: try!(parse_base64url(data, "credentialId")),
+            is_resident_credential: is_resident_credential,
+            rp_id: rp_id,
+            private_key: try!(parse_base64url(data, "privateKey")),
+            user_handle: user_handle,
+            sign_count: sign_count,
+        })
+    }
+}
+
+impl ToJson for CredentialParameters {
+    fn to_json(&self) -> Json {
+        let mut data = BTreeMap::new();
+        data.insert("credentialId".to_owned(), self.credential_id.to_json());
+        data.insert("isResidentCredential".to_owned(), self.is_resident_credential.to_json());
+        data.insert("rpId".to_owned(), self.rp_id.to_json());
+        data.insert("privateKey".to_owned(), self.private_key.to_json());
+        if let Some(ref user_handle) = self.user_handle {
+            data.insert("userHandle".to_owned(), user_handle.to_json());
+        }
+        data.insert("signCount".to_owned(), self.sign_count.to_json());
+        Json::Object(data)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct UserVerificationParameters {
+    pub is_user_verified: bool,
+}
+
+impl Parameters for UserVerificationParameters {
+    fn from_json(body: &Json) -> WebDriverResult<UserVerificationParameters> {
+        let data = try_opt!(body.as_object(),
+                            ErrorStatus::InvalidArgument,
+                            "Message body was not an object");
+        let is_user_verified = try_opt!(
+            try_opt!(data.get("isUserVerified"),
+                     ErrorStatus::InvalidArgument,
+                     "Missing 'isUserVerified' parameter").as_boolean(),
+            ErrorStatus::InvalidArgument,
+            "Parameter 'isUserVerified' was not a boolean");
+        Ok(UserVerificationParameters {
+            is_user_verified: is_user_verified,
+        })
+    }
+}
+
+impl ToJson for UserVerificationParameters {
+    fn to_json(&self) -> Json {
+        let mut data = BTreeMap::new();
+        data.insert("isUserVerified".to_owned(), self.is_user_verified.to_json());
+        Json::Object(data)
+    }
+}
+
+#[derive(Clone, PartialEq)]
 pub struct ActionsParameters {
     pub actions: Vec<ActionSequence>
 }
@@ -1058,7 +1995,7 @@ impl ToJson for ActionsParameters {
     }
 }
 
-#[derive(PartialEq)]
+#[derive(Clone, PartialEq)]
 pub struct ActionSequence {
     pub id: Nullable<String>,
     pub actions: ActionsType
@@ -1087,7 +2024,7 @@ impl Parameters for ActionSequence {
         // Note that unlike the spec we get the pointer parameters in ActionsType::from_json
 
         let actions = match type_name {
-            "none" | "key" | "pointer" => try!(ActionsType::from_json(&body)),
+            "none" | "key" | "pointer" | "wheel" => try!(ActionsType::from_json(&body)),
             _ => return Err(WebDriverError::new(ErrorStatus::InvalidArgument,
                                                 "Invalid action type"))
         };
@@ -1102,7 +2039,9 @@ impl Parameters for ActionSequence {
 impl ToJson for ActionSequence {
     fn to_json(&self) -> Json {
         let mut data: BTreeMap<String, Json> = BTreeMap::new();
-        data.insert("id".into(), self.id.to_json());
+        if let Nullable::Value(ref id) = self.id {
+            data.insert("id".into(), id.to_json());
+        }
         let (action_type, actions) = match self.actions {
             ActionsType::Null(ref actions) => {
                 ("none",
@@ -1117,6 +2056,10 @@ impl ToJson for ActionSequence {
                 ("pointer",
                  actions.iter().map(|x| x.to_json()).collect::<Vec<Json>>())
             }
+            ActionsType::Wheel(ref actions) => {
+                ("wheel",
+                 actions.iter().map(|x| x.to_json()).collect::<Vec<Json>>())
+            }
         };
         data.insert("type".into(), action_type.to_json());
         data.insert("actions".into(), actions.to_json());
@@ -1124,11 +2067,12 @@ impl ToJson for ActionSequence {
     }
 }
 
-#[derive(PartialEq)]
+#[derive(Clone, PartialEq)]
 pub enum ActionsType {
     Null(Vec<NullActionItem>),
     Key(Vec<KeyActionItem>),
-    Pointer(PointerActionParameters, Vec<PointerActionItem>)
+    Pointer(PointerActionParameters, Vec<PointerActionItem>),
+    Wheel(Vec<WheelActionItem>)
 }
 
 impl Parameters for ActionsType {
@@ -1167,13 +2111,20 @@ impl Parameters for ActionsType {
                     actions.push(try!(PointerActionItem::from_json(action_body)));
                 }
                 Ok(ActionsType::Pointer(parameters, actions))
+            },
+            "wheel" => {
+                let mut actions = Vec::with_capacity(actions_chain.len());
+                for action_body in actions_chain.iter() {
+                    actions.push(try!(WheelActionItem::from_json(action_body)));
+                }
+                Ok(ActionsType::Wheel(actions))
             }
             _ => panic!("Got unexpected action type after checking type")
         }
     }
 }
 
-#[derive(PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum PointerType {
     Mouse,
     Pen,
@@ -1214,7 +2165,7 @@ impl Default for PointerType {
     }
 }
 
-#[derive(Default, PartialEq)]
+#[derive(Clone, Default, PartialEq)]
 pub struct PointerActionParameters {
     pub pointer_type: PointerType
 }
@@ -1243,7 +2194,7 @@ impl ToJson for PointerActionParameters {
     }
 }
 
-#[derive(PartialEq)]
+#[derive(Clone, PartialEq)]
 pub enum NullActionItem {
     General(GeneralAction)
 }
@@ -1276,7 +2227,7 @@ impl ToJson for NullActionItem {
     }
 }
 
-#[derive(PartialEq)]
+#[derive(Clone, PartialEq)]
 pub enum KeyActionItem {
     General(GeneralAction),
     Key(KeyAction)
@@ -1311,7 +2262,7 @@ impl ToJson for KeyActionItem {
     }
 }
 
-#[derive(PartialEq)]
+#[derive(Clone, PartialEq)]
 pub enum PointerActionItem {
     General(GeneralAction),
     Pointer(PointerAction)
@@ -1345,7 +2296,7 @@ impl ToJson for PointerActionItem {
     }
 }
 
-#[derive(PartialEq)]
+#[derive(Clone, PartialEq)]
 pub enum GeneralAction {
     Pause(PauseAction)
 }
@@ -1368,7 +2319,7 @@ impl ToJson for GeneralAction {
     }
 }
 
-#[derive(PartialEq)]
+#[derive(Clone, PartialEq)]
 pub struct PauseAction {
     pub duration: u64
 }
@@ -1395,7 +2346,7 @@ impl ToJson for PauseAction {
     }
 }
 
-#[derive(PartialEq)]
+#[derive(Clone, PartialEq)]
 pub enum KeyAction {
     Up(KeyUpAction),
     Down(KeyDownAction)
@@ -1438,7 +2389,7 @@ fn validate_key_value(value_str: &str) -> WebDriverResult<char> {
     Ok(value)
 }
 
-#[derive(PartialEq)]
+#[derive(Clone, PartialEq)]
 pub struct KeyUpAction {
     pub value: char
 }
@@ -1470,7 +2421,7 @@ impl ToJson for KeyUpAction {
     }
 }
 
-#[derive(PartialEq)]
+#[derive(Clone, PartialEq)]
 pub struct KeyDownAction {
     pub value: char
 }
@@ -1501,7 +2452,7 @@ impl ToJson for KeyDownAction {
     }
 }
 
-#[derive(PartialEq)]
+#[derive(Clone, PartialEq)]
 pub enum PointerAction {
     Up(PointerUpAction),
     Down(PointerDownAction),
@@ -1539,7 +2490,7 @@ impl ToJson for PointerAction {
     }
 }
 
-#[derive(PartialEq)]
+#[derive(Clone, PartialEq)]
 pub struct PointerUpAction {
     pub button: u64,
 }
@@ -1569,7 +2520,7 @@ impl ToJson for PointerUpAction {
     }
 }
 
-#[derive(PartialEq)]
+#[derive(Clone, PartialEq)]
 pub struct PointerDownAction {
     pub button: u64,
 }
@@ -1599,7 +2550,7 @@ impl ToJson for PointerDownAction {
     }
 }
 
-#[derive(PartialEq)]
+#[derive(Clone, PartialEq)]
 pub struct PointerMoveAction {
     pub duration: Nullable<u64>,
     pub element: Nullable<WebElement>,
@@ -1670,3 +2621,322 @@ impl ToJson for PointerMoveAction {
         Json::Object(data)
     }
 }
+
+#[derive(Clone, PartialEq)]
+pub enum WheelActionItem {
+    General(GeneralAction),
+    Wheel(WheelAction)
+}
+
+impl Parameters for WheelActionItem {
+    fn from_json(body: &Json) -> WebDriverResult<WheelActionItem> {
+        let data = try_opt!(body.as_object(),
+                            ErrorStatus::InvalidArgument,
+                            "Wheel action item was not an object");
+        let type_name = try_opt!(
+            try_opt!(data.get("type"),
+                     ErrorStatus::InvalidArgument,
+                     "Missing 'type' parameter").as_string(),
+            ErrorStatus::InvalidArgument,
+            "Parameter 'type' was not a string");
+
+        match type_name {
+            "pause" => Ok(WheelActionItem::General(try!(GeneralAction::from_json(body)))),
+            _ => Ok(WheelActionItem::Wheel(try!(WheelAction::from_json(body))))
+        }
+    }
+}
+
+impl ToJson for WheelActionItem {
+    fn to_json(&self) -> Json {
+        match self {
+            &WheelActionItem::General(ref x) => x.to_json(),
+            &WheelActionItem::Wheel(ref x) => x.to_json()
+        }
+    }
+}
+
+#[derive(Clone, PartialEq)]
+pub enum WheelAction {
+    Scroll(WheelScrollAction)
+}
+
+impl Parameters for WheelAction {
+    fn from_json(body: &Json) -> WebDriverResult<WheelAction> {
+        match body.find("type").and_then(|x| x.as_string()) {
+            Some("scroll") => Ok(WheelAction::Scroll(try!(WheelScrollAction::from_json(body)))),
+            Some(_) | None => Err(WebDriverError::new(
+                ErrorStatus::InvalidArgument,
+                "Missing or invalid type argument for wheel action"))
+        }
+    }
+}
+
+impl ToJson for WheelAction {
+    fn to_json(&self) -> Json {
+        match self {
+            &WheelAction::Scroll(ref x) => x.to_json()
+        }
+    }
+}
+
+/// Scroll by `delta_x` and `delta_y` at a point given by `x` and `y`, which
+/// are relative to the viewport, or to the center of `element` if set.
+#[derive(Clone, PartialEq)]
+pub struct WheelScrollAction {
+    pub duration: Nullable<u64>,
+    /// The element the coordinates are relative to, or `Null` for the
+    /// viewport.
+    pub origin: Nullable<WebElement>,
+    pub x: i64,
+    pub y: i64,
+    pub delta_x: i64,
+    pub delta_y: i64,
+}
+
+impl Parameters for WheelScrollAction {
+    fn from_json(body: &Json) -> WebDriverResult<WheelScrollAction> {
+        let duration = match body.find("duration") {
+            Some(duration) => Some(try_opt!(duration.as_u64(),
+                                            ErrorStatus::InvalidArgument,
+                                            "Parameter 'duration' was not a positive integer")),
+            None => None
+        };
+
+        let origin = match body.find("origin") {
+            Some(&Json::String(ref x)) if x == "viewport" => None,
+            Some(&Json::String(ref x)) => {
+                return Err(WebDriverError::new(ErrorStatus::InvalidArgument,
+                                               format!("Unknown origin {}", x)));
+            },
+            Some(origin) => Some(try!(WebElement::from_json(origin))),
+            None => None
+        };
+
+        let integer = |name: &str| -> WebDriverResult<i64> {
+            Ok(try_opt!(try_opt!(body.find(name),
+                                 ErrorStatus::InvalidArgument,
+                                 format!("Missing {} parameter", name)).as_i64(),
+                        ErrorStatus::InvalidArgument,
+                        format!("Parameter '{}' was not an integer", name)))
+        };
+
+        Ok(WheelScrollAction {
+            duration: duration.into(),
+            origin: origin.into(),
+            x: try!(integer("x")),
+            y: try!(integer("y")),
+            delta_x: try!(integer("deltaX")),
+            delta_y: try!(integer("deltaY")),
+        })
+    }
+}
+
+impl ToJson for WheelScrollAction {
+    fn to_json(&self) -> Json {
+        let mut data = BTreeMap::new();
+        data.insert("type".to_owned(), "scroll".to_json());
+        if self.duration.is_value() {
+            data.insert("duration".to_owned(),
+                        self.duration.to_json());
+        }
+        data.insert("origin".to_owned(), match self.origin {
+            Nullable::Value(ref x) => x.to_json(),
+            Nullable::Null => "viewport".to_json(),
+        });
+        data.insert("x".to_owned(), self.x.to_json());
+        data.insert("y".to_owned(), self.y.to_json());
+        data.insert("deltaX".to_owned(), self.delta_x.to_json());
+        data.insert("deltaY".to_owned(), self.delta_y.to_json());
+        Json::Object(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::{Nullable, WebElement, WindowType};
+    use rustc_serialize::json::{Json, ToJson};
+    use super::{ActionsParameters, ActionsType, AuthenticatorParameters, AuthenticatorProtocol,
+                AuthenticatorTransport, CredentialParameters, NewWindowParameters, PageRange,
+                Parameters,
+                PermissionState, PrintOrientation, PrintParameters, SetPermissionParameters,
+                WheelAction, WheelActionItem, WheelScrollAction, WindowRectParameters};
+
+    #[test]
+    fn test_window_rect_parameters() {
+        let body = Json::from_str(r#"{"x": -1920, "y": -40, "width": 800, "height": null}"#);
+        let parameters = WindowRectParameters::from_json(&body.unwrap()).unwrap();
+        assert_eq!(parameters.x, Nullable::Value(-1920));
+        assert_eq!(parameters.y, Nullable::Value(-40));
+        assert_eq!(parameters.width, Nullable::Value(800));
+        assert_eq!(parameters.height, Nullable::Null);
+
+        assert!(WindowRectParameters::from_json(&parameters.to_json()).unwrap() == parameters);
+        assert_eq!(parameters.to_json(),
+                   Json::from_str(r#"{"x": -1920, "y": -40, "width": 800, "height": null}"#)
+                       .unwrap());
+
+        let parameters = WindowRectParameters::from_json(&Json::from_str("{}").unwrap()).unwrap();
+        assert_eq!((parameters.x, parameters.y), (Nullable::Null, Nullable::Null));
+        assert_eq!((parameters.width, parameters.height), (Nullable::Null, Nullable::Null));
+
+        for body in &[r#"{"x": 2147483648}"#, r#"{"y": -2147483649}"#, r#"{"x": 1.5}"#,
+                      r#"{"width": -1}"#, r#"{"height": "1"}"#, r#"{"width": 2147483648}"#,
+                      r#"[]"#] {
+            assert!(WindowRectParameters::from_json(&Json::from_str(body).unwrap()).is_err());
+        }
+    }
+
+    #[test]
+    fn test_new_window_parameters() {
+        let parameters = NewWindowParameters::from_json(&Json::from_str("{}").unwrap()).unwrap();
+        assert_eq!(parameters.type_hint, None);
+        assert_eq!(parameters.to_json(), Json::from_str("{}").unwrap());
+
+        let body = Json::from_str(r#"{"type": "tab"}"#).unwrap();
+        let parameters = NewWindowParameters::from_json(&body).unwrap();
+        assert_eq!(parameters.type_hint, Some(WindowType::Tab));
+        assert_eq!(parameters.to_json(), body);
+
+        let body = Json::from_str(r#"{"type": "window"}"#).unwrap();
+        let parameters = NewWindowParameters::from_json(&body).unwrap();
+        assert_eq!(parameters.type_hint, Some(WindowType::Window));
+        assert_eq!(parameters.to_json(), body);
+
+        // The type is only a hint, so unknown values fall back to the default
+        let body = Json::from_str(r#"{"type": "popup"}"#).unwrap();
+        assert_eq!(NewWindowParameters::from_json(&body).unwrap().type_hint, None);
+
+        for body in &[r#"{"type": 1}"#, r#"{"type": ["tab"]}"#, r#""tab""#] {
+            let body = Json::from_str(body).unwrap();
+            assert!(NewWindowParameters::from_json(&body).is_err());
+        }
+    }
+
+    #[test]
+    fn test_print_parameters() {
+        let body = Json::from_str(r#"{"orientation": "landscape", "scale": 0.5,
+                                      "page": {"width": 10},
+                                      "pageRanges": [2, "4-6", "-3", "8-"]}"#);
+        let parameters = PrintParameters::from_json(&body.unwrap()).unwrap();
+        assert_eq!(parameters.orientation, PrintOrientation::Landscape);
+        assert_eq!(parameters.page.width, 10.0);
+        assert_eq!(parameters.page.height, 27.94);
+        assert!(parameters.shrink_to_fit);
+        assert_eq!(parameters.page_ranges,
+                   vec![PageRange { start: 2, end: Some(2) },
+                        PageRange { start: 4, end: Some(6) },
+                        PageRange { start: 1, end: Some(3) },
+                        PageRange { start: 8, end: None }]);
+        assert_eq!(PrintParameters::from_json(&parameters.to_json()).unwrap(), parameters);
+
+        for body in &[r#"{"scale": 2.5}"#, r#"{"scale": 0.05}"#,
+                      r#"{"margin": {"top": -1}}"#, r#"{"page": {"height": 0}}"#,
+                      r#"{"pageRanges": ["3-1"]}"#, r#"{"pageRanges": ["1-2-3"]}"#,
+                      r#"{"pageRanges": ["a"]}"#, r#"{"orientation": "sideways"}"#,
+                      r#"{"pageRanges": [0]}"#, r#"{"pageRanges": ["0"]}"#,
+                      r#"{"pageRanges": ["0-2"]}"#, r#"{"pageRanges": ["5-2"]}"#,
+                      r#"{"page": {"width": 5}, "margin": {"left": 3, "right": 2}}"#,
+                      r#"{"page": {"height": 5}, "margin": {"top": 4, "bottom": 1.5}}"#] {
+            assert!(PrintParameters::from_json(&Json::from_str(body).unwrap()).is_err());
+        }
+    }
+
+    #[test]
+    fn test_authenticator_parameters() {
+        let body = Json::from_str(r#"{"protocol": "ctap1/u2f", "transport": "usb",
+                                      "hasResidentKey": true}"#);
+        let parameters = AuthenticatorParameters::from_json(&body.unwrap()).unwrap();
+        assert_eq!(parameters.protocol, AuthenticatorProtocol::Ctap1U2f);
+        assert_eq!(parameters.transport, AuthenticatorTransport::Usb);
+        assert!(parameters.has_resident_key);
+        assert!(!parameters.has_user_verification);
+        assert!(parameters.is_user_consenting);
+        assert_eq!(AuthenticatorParameters::from_json(&parameters.to_json()).unwrap(), parameters);
+
+        for body in &[r#"{"protocol": "ctap3", "transport": "usb"}"#,
+                      r#"{"protocol": "ctap2", "transport": "carrier-pigeon"}"#,
+                      r#"{"transport": "usb"}"#,
+                      r#"{"protocol": "ctap2", "transport": "usb", "hasResidentKey": 1}"#] {
+            assert!(AuthenticatorParameters::from_json(&Json::from_str(body).unwrap()).is_err());
+        }
+    }
+
+    #[test]
+    fn test_credential_parameters() {
+        let body = Json::from_str(r#"{"credentialId": "cmVzaWRlbnQ", "isResidentCredential": true,
+                                      "rpId": "example.org", "privateKey": "a2V5-_w",
+                                      "userHandle": "dXNlcg", "signCount": 0}"#);
+        let parameters = CredentialParameters::from_json(&body.unwrap()).unwrap();
+        assert_eq!(parameters.credential_id, "cmVzaWRlbnQ");
+        assert_eq!(parameters.user_handle, Some("dXNlcg".into()));
+        assert_eq!(CredentialParameters::from_json(&parameters.to_json()).unwrap(), parameters);
+
+        for credential_id in &["cmVzaWRlbnQ=", "a+b/", "a", "not base64"] {
+            let body = Json::from_str(&format!(
+                r#"{{"credentialId": "{}", "isResidentCredential": false,
+                    "rpId": "example.org", "privateKey": "a2V5", "signCount": 0}}"#,
+                credential_id));
+            assert!(CredentialParameters::from_json(&body.unwrap()).is_err());
+        }
+    }
+
+    #[test]
+    fn test_set_permission_parameters() {
+        let body = Json::from_str(r#"{"descriptor": {"name": "push", "userVisibleOnly": true},
+                                      "state": "granted"}"#);
+        let parameters = SetPermissionParameters::from_json(&body.unwrap()).unwrap();
+        assert_eq!(parameters.descriptor.name, "push");
+        assert_eq!(parameters.descriptor.extra.get("userVisibleOnly"), Some(&Json::Boolean(true)));
+        assert_eq!(parameters.state, PermissionState::Granted);
+        assert_eq!(SetPermissionParameters::from_json(&parameters.to_json()).unwrap(), parameters);
+
+        for body in &[r#"{"descriptor": {"name": "camera"}, "state": "allowed"}"#,
+                      r#"{"descriptor": {}, "state": "denied"}"#,
+                      r#"{"descriptor": "camera", "state": "denied"}"#,
+                      r#"{"descriptor": {"name": "camera"}}"#] {
+            assert!(SetPermissionParameters::from_json(&Json::from_str(body).unwrap()).is_err());
+        }
+    }
+
+    #[test]
+    fn test_wheel_actions() {
+        let body = Json::from_str(r#"{"actions": [{"type": "wheel", "id": "wheel", "actions": [
+                                          {"type": "pause", "duration": 5},
+                                          {"type": "scroll", "x": 10, "y": 20,
+                                           "deltaX": 0, "deltaY": -100}]}]}"#);
+        let parameters = ActionsParameters::from_json(&body.unwrap()).unwrap();
+        match parameters.actions[0].actions {
+            ActionsType::Wheel(ref actions) => match actions[1] {
+                WheelActionItem::Wheel(WheelAction::Scroll(ref x)) => {
+                    assert_eq!((x.x, x.y, x.delta_x, x.delta_y), (10, 20, 0, -100));
+                    assert_eq!(x.duration, Nullable::Null);
+                },
+                _ => panic!("Expected a scroll action")
+            },
+            _ => panic!("Expected a wheel input source")
+        }
+        assert!(ActionsParameters::from_json(&parameters.to_json()).unwrap() == parameters);
+
+        let body = Json::from_str(r#"{"type": "scroll", "x": 0, "y": 0, "deltaX": 0, "deltaY": 5,
+                                      "origin": {"element-6066-11e4-a52e-4f735466cecf": "e1"}}"#);
+        let action = WheelScrollAction::from_json(&body.unwrap()).unwrap();
+        assert_eq!(action.origin, Nullable::Value(WebElement::new("e1".into())));
+        assert_eq!(action.to_json().find("origin"),
+                   Some(&WebElement::new("e1".into()).to_json()));
+
+        let body = Json::from_str(r#"{"type": "scroll", "x": 0, "y": 0, "deltaX": 0, "deltaY": 5,
+                                      "origin": "viewport"}"#);
+        let action = WheelScrollAction::from_json(&body.unwrap()).unwrap();
+        assert_eq!(action.origin, Nullable::Null);
+        assert_eq!(action.to_json().find("origin"), Some(&"viewport".to_json()));
+
+        for body in &[r#"{"actions": [{"type": "wheel", "id": "wheel", "actions": [
+                          {"type": "scroll", "x": 0, "y": 0, "deltaX": 1}]}]}"#,
+                      r#"{"actions": [{"type": "wheel", "id": "wheel", "actions": [
+                          {"type": "scroll", "x": 0, "y": 0, "deltaX": 1, "deltaY": 1,
+                           "origin": "pointer"}]}]}"#] {
+            assert!(ActionsParameters::from_json(&Json::from_str(body).unwrap()).is_err());
+        }
+    }
+}
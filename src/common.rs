@@ -1,10 +1,31 @@
 use rustc_serialize::{Encodable, Encoder};
 use rustc_serialize::json::{Json, ToJson};
 use std::collections::BTreeMap;
+use std::thread;
+use std::time::{Duration, Instant};
 
 use error::{WebDriverResult, WebDriverError, ErrorStatus};
 
 pub static ELEMENT_KEY: &'static str = "element-6066-11e4-a52e-4f735466cecf";
+pub static SHADOW_KEY: &'static str = "shadow-6066-11e4-a52e-4f735466cecf";
+
+/// A source of time for the waits done by the server and the client.
+pub trait Clock: Send {
+    fn now(&self) -> Instant;
+    fn sleep(&self, duration: Duration);
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        thread::sleep(duration)
+    }
+}
 
 #[derive(RustcEncodable, PartialEq, Clone, Debug)]
 pub struct Date(pub u64);
@@ -142,6 +163,49 @@ impl <T> From<T> for WebElement
     }
 }
 
+#[derive(Clone, Debug, PartialEq)]
+pub struct ShadowRoot {
+    pub id: String
+}
+
+impl ShadowRoot {
+    pub fn new(id: String) -> ShadowRoot {
+        ShadowRoot {
+            id: id
+        }
+    }
+
+    pub fn from_json(data: &Json) -> WebDriverResult<ShadowRoot> {
+        let object = try_opt!(data.as_object(),
+                              ErrorStatus::InvalidArgument,
+                              "Could not convert shadow root to object");
+        let id_value = try_opt!(object.get(SHADOW_KEY),
+                                ErrorStatus::InvalidArgument,
+                                "Could not find shadow root key");
+
+        let id = try_opt!(id_value.as_string(),
+                          ErrorStatus::InvalidArgument,
+                          "Could not convert shadow root to string").to_string();
+
+        Ok(ShadowRoot::new(id))
+    }
+}
+
+impl ToJson for ShadowRoot {
+    fn to_json(&self) -> Json {
+        let mut data = BTreeMap::new();
+        data.insert(SHADOW_KEY.to_string(), self.id.to_json());
+        Json::Object(data)
+    }
+}
+
+impl <T> From<T> for ShadowRoot
+    where T: Into<String> {
+    fn from(data: T) -> ShadowRoot {
+        ShadowRoot::new(data.into())
+    }
+}
+
 #[derive(PartialEq, Debug)]
 pub enum FrameId {
     Short(u16),
@@ -184,7 +248,35 @@ impl ToJson for FrameId {
     }
 }
 
-#[derive(PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
+pub enum WindowType {
+    Tab,
+    Window,
+}
+
+impl WindowType {
+    pub fn from_json(body: &Json) -> WebDriverResult<WindowType> {
+        match try_opt!(body.as_string(),
+                       ErrorStatus::InvalidArgument,
+                       "Window type was not a string") {
+            "tab" => Ok(WindowType::Tab),
+            "window" => Ok(WindowType::Window),
+            x => Err(WebDriverError::new(ErrorStatus::InvalidArgument,
+                                         format!("Unknown window type {}", x)))
+        }
+    }
+}
+
+impl ToJson for WindowType {
+    fn to_json(&self) -> Json {
+        Json::String(match *self {
+            WindowType::Tab => "tab",
+            WindowType::Window => "window",
+        }.to_string())
+    }
+}
+
+#[derive(Clone, PartialEq)]
 pub enum LocatorStrategy {
     CSSSelector,
     LinkText,
@@ -1,8 +1,10 @@
 use backtrace::Backtrace;
+use hyper::Error as HttpError;
 use hyper::status::StatusCode;
 use rustc_serialize::base64::FromBase64Error;
 use rustc_serialize::json::{DecoderError, Json, ParserError, ToJson};
 use std::borrow::Cow;
+use std::cell::Cell;
 use std::collections::BTreeMap;
 use std::convert::From;
 use std::error::Error;
@@ -11,6 +13,9 @@ use std::io::Error as IoError;
 
 #[derive(PartialEq, Debug)]
 pub enum ErrorStatus {
+    DetachedShadowRoot,
+    ElementClickIntercepted,
+    ElementNotInteractable,
     ElementNotSelectable,
     ElementNotVisible,
     InsecureCertificate,
@@ -23,24 +28,34 @@ pub enum ErrorStatus {
     JavascriptError,
     MoveTargetOutOfBounds,
     NoSuchAlert,
+    NoSuchCookie,
     NoSuchElement,
     NoSuchFrame,
+    NoSuchShadowRoot,
     NoSuchWindow,
     ScriptTimeout,
     SessionNotCreated,
     StaleElementReference,
     Timeout,
+    UnableToCaptureScreen,
     UnableToSetCookie,
     UnexpectedAlertOpen,
     UnknownError,
     UnknownMethod,
     UnknownPath,
     UnsupportedOperation,
+    /// An error code this library doesn't know, received from a remote end.
+    Unrecognized(String),
 }
 
 impl ErrorStatus {
+    /// The W3C error code. An unrecognized code is reported as
+    /// `unknown error`; use `code` to get it as received.
     pub fn status_code(&self) -> &'static str {
         match self {
+            &ErrorStatus::DetachedShadowRoot => "detached shadow root",
+            &ErrorStatus::ElementClickIntercepted => "element click intercepted",
+            &ErrorStatus::ElementNotInteractable => "element not interactable",
             &ErrorStatus::ElementNotSelectable => "element not selectable",
             &ErrorStatus::ElementNotVisible => "element not visible",
             &ErrorStatus::InsecureCertificate => "insecure certificate",
@@ -53,24 +68,39 @@ impl ErrorStatus {
             &ErrorStatus::JavascriptError => "javascript error",
             &ErrorStatus::MoveTargetOutOfBounds => "move target out of bounds",
             &ErrorStatus::NoSuchAlert => "no such alert",
+            &ErrorStatus::NoSuchCookie => "no such cookie",
             &ErrorStatus::NoSuchElement => "no such element",
             &ErrorStatus::NoSuchFrame => "no such frame",
+            &ErrorStatus::NoSuchShadowRoot => "no such shadow root",
             &ErrorStatus::NoSuchWindow => "no such window",
             &ErrorStatus::ScriptTimeout => "script timeout",
             &ErrorStatus::SessionNotCreated => "session not created",
             &ErrorStatus::StaleElementReference => "stale element reference",
             &ErrorStatus::Timeout => "timeout",
+            &ErrorStatus::UnableToCaptureScreen => "unable to capture screen",
             &ErrorStatus::UnableToSetCookie => "unable to set cookie",
             &ErrorStatus::UnexpectedAlertOpen => "unexpected alert open",
             &ErrorStatus::UnknownError => "unknown error",
-            &ErrorStatus::UnknownMethod => "unknown command",
+            &ErrorStatus::UnknownMethod => "unknown method",
             &ErrorStatus::UnknownPath => "unknown command",
             &ErrorStatus::UnsupportedOperation => "unsupported operation",
+            &ErrorStatus::Unrecognized(_) => "unknown error",
+        }
+    }
+
+    /// The error code, including one that this library doesn't know.
+    pub fn code(&self) -> &str {
+        match self {
+            &ErrorStatus::Unrecognized(ref code) => code,
+            _ => self.status_code(),
         }
     }
 
     pub fn http_status(&self) -> StatusCode {
         match self {
+            &ErrorStatus::DetachedShadowRoot => StatusCode::NotFound,
+            &ErrorStatus::ElementClickIntercepted => StatusCode::BadRequest,
+            &ErrorStatus::ElementNotInteractable => StatusCode::BadRequest,
             &ErrorStatus::ElementNotSelectable => StatusCode::BadRequest,
             &ErrorStatus::ElementNotVisible => StatusCode::BadRequest,
             &ErrorStatus::InsecureCertificate => StatusCode::BadRequest,
@@ -83,31 +113,181 @@ impl ErrorStatus {
             &ErrorStatus::JavascriptError => StatusCode::InternalServerError,
             &ErrorStatus::MoveTargetOutOfBounds => StatusCode::InternalServerError,
             &ErrorStatus::NoSuchAlert => StatusCode::BadRequest,
+            &ErrorStatus::NoSuchCookie => StatusCode::NotFound,
             &ErrorStatus::NoSuchElement => StatusCode::NotFound,
             &ErrorStatus::NoSuchFrame => StatusCode::BadRequest,
+            &ErrorStatus::NoSuchShadowRoot => StatusCode::NotFound,
             &ErrorStatus::NoSuchWindow => StatusCode::BadRequest,
             &ErrorStatus::ScriptTimeout => StatusCode::RequestTimeout,
             &ErrorStatus::SessionNotCreated => StatusCode::InternalServerError,
             &ErrorStatus::StaleElementReference => StatusCode::BadRequest,
             &ErrorStatus::Timeout => StatusCode::RequestTimeout,
+            &ErrorStatus::UnableToCaptureScreen => StatusCode::InternalServerError,
             &ErrorStatus::UnableToSetCookie => StatusCode::InternalServerError,
             &ErrorStatus::UnexpectedAlertOpen => StatusCode::InternalServerError,
             &ErrorStatus::UnknownError => StatusCode::InternalServerError,
             &ErrorStatus::UnknownMethod => StatusCode::MethodNotAllowed,
             &ErrorStatus::UnknownPath => StatusCode::NotFound,
             &ErrorStatus::UnsupportedOperation => StatusCode::InternalServerError,
+            &ErrorStatus::Unrecognized(_) => StatusCode::InternalServerError,
         }
     }
+
+    /// Look up the status for a W3C error code, as sent in the `error` field
+    /// of an error response.
+    pub fn from_code(code: &str) -> Option<ErrorStatus> {
+        let status = match code {
+            "detached shadow root" => ErrorStatus::DetachedShadowRoot,
+            "element click intercepted" => ErrorStatus::ElementClickIntercepted,
+            "element not interactable" => ErrorStatus::ElementNotInteractable,
+            "element not selectable" => ErrorStatus::ElementNotSelectable,
+            "element not visible" => ErrorStatus::ElementNotVisible,
+            "insecure certificate" => ErrorStatus::InsecureCertificate,
+            "invalid argument" => ErrorStatus::InvalidArgument,
+            "invalid cookie domain" => ErrorStatus::InvalidCookieDomain,
+            "invalid element coordinates" => ErrorStatus::InvalidElementCoordinates,
+            "invalid element state" => ErrorStatus::InvalidElementState,
+            "invalid selector" => ErrorStatus::InvalidSelector,
+            "invalid session id" => ErrorStatus::InvalidSessionId,
+            "javascript error" => ErrorStatus::JavascriptError,
+            "move target out of bounds" => ErrorStatus::MoveTargetOutOfBounds,
+            "no such alert" => ErrorStatus::NoSuchAlert,
+            "no such cookie" => ErrorStatus::NoSuchCookie,
+            "no such element" => ErrorStatus::NoSuchElement,
+            "no such frame" => ErrorStatus::NoSuchFrame,
+            "no such shadow root" => ErrorStatus::NoSuchShadowRoot,
+            "no such window" => ErrorStatus::NoSuchWindow,
+            "script timeout" => ErrorStatus::ScriptTimeout,
+            "session not created" => ErrorStatus::SessionNotCreated,
+            "stale element reference" => ErrorStatus::StaleElementReference,
+            "timeout" => ErrorStatus::Timeout,
+            "unable to capture screen" => ErrorStatus::UnableToCaptureScreen,
+            "unable to set cookie" => ErrorStatus::UnableToSetCookie,
+            "unexpected alert open" => ErrorStatus::UnexpectedAlertOpen,
+            "unknown command" => ErrorStatus::UnknownPath,
+            "unknown error" => ErrorStatus::UnknownError,
+            "unknown method" => ErrorStatus::UnknownMethod,
+            "unsupported operation" => ErrorStatus::UnsupportedOperation,
+            _ => return None
+        };
+        Some(status)
+    }
+
+    /// Look up the status for a numeric code of the legacy JSON Wire
+    /// Protocol, including the codes added by chromedriver.
+    pub fn from_legacy_code(code: u64) -> Option<ErrorStatus> {
+        let status = match code {
+            6 => ErrorStatus::InvalidSessionId,
+            7 => ErrorStatus::NoSuchElement,
+            8 => ErrorStatus::NoSuchFrame,
+            9 => ErrorStatus::UnknownPath,
+            10 => ErrorStatus::StaleElementReference,
+            11 => ErrorStatus::ElementNotVisible,
+            12 => ErrorStatus::InvalidElementState,
+            13 => ErrorStatus::UnknownError,
+            15 => ErrorStatus::ElementNotSelectable,
+            17 => ErrorStatus::JavascriptError,
+            19 => ErrorStatus::InvalidSelector,
+            21 => ErrorStatus::Timeout,
+            23 => ErrorStatus::NoSuchWindow,
+            24 => ErrorStatus::InvalidCookieDomain,
+            25 => ErrorStatus::UnableToSetCookie,
+            26 => ErrorStatus::UnexpectedAlertOpen,
+            27 => ErrorStatus::NoSuchAlert,
+            28 => ErrorStatus::ScriptTimeout,
+            29 => ErrorStatus::InvalidElementCoordinates,
+            32 => ErrorStatus::InvalidSelector,
+            33 => ErrorStatus::SessionNotCreated,
+            34 => ErrorStatus::MoveTargetOutOfBounds,
+            51 => ErrorStatus::InvalidSelector,
+            52 => ErrorStatus::InvalidSelector,
+            60 => ErrorStatus::ElementNotInteractable,
+            61 => ErrorStatus::InvalidArgument,
+            62 => ErrorStatus::NoSuchCookie,
+            63 => ErrorStatus::UnableToCaptureScreen,
+            64 => ErrorStatus::ElementClickIntercepted,
+            _ => return None
+        };
+        Some(status)
+    }
 }
 
 pub type WebDriverResult<T> = Result<T, WebDriverError>;
 
+/// How `WebDriverError::new` captures a backtrace.
+///
+/// This is a per-thread setting, changed with `set_backtrace_capture`; the
+/// servers install their configured value on the threads they run.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BacktraceCapture {
+    /// Capture the backtrace and resolve its symbols.
+    Eager,
+    /// Capture only the frames, resolving symbols when the error is
+    /// serialized.
+    Lazy,
+    /// Don't capture a backtrace.
+    Disabled,
+}
+
+impl Default for BacktraceCapture {
+    fn default() -> BacktraceCapture {
+        BacktraceCapture::Eager
+    }
+}
+
+/// What is sent to the client in the `stacktrace` field of an error.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StacktraceReporting {
+    /// The Rust backtrace of the error, if one was captured.
+    Backtrace,
+    /// The stacktrace provided by the handler, typically a browser-side
+    /// JavaScript stack, or an empty string if there is none.
+    Provided,
+    /// Always an empty string.
+    Redacted,
+}
+
+impl Default for StacktraceReporting {
+    fn default() -> StacktraceReporting {
+        StacktraceReporting::Backtrace
+    }
+}
+
+thread_local! {
+    static BACKTRACE_CAPTURE: Cell<BacktraceCapture> = Cell::new(BacktraceCapture::default());
+}
+
+/// Set how errors created on the current thread capture a backtrace.
+pub fn set_backtrace_capture(capture: BacktraceCapture) {
+    BACKTRACE_CAPTURE.with(|x| x.set(capture));
+}
+
+/// How errors created on the current thread capture a backtrace.
+pub fn backtrace_capture() -> BacktraceCapture {
+    BACKTRACE_CAPTURE.with(|x| x.get())
+}
+
+fn capture_backtrace(capture: BacktraceCapture) -> Option<Backtrace> {
+    match capture {
+        BacktraceCapture::Eager => Some(Backtrace::new()),
+        BacktraceCapture::Lazy => Some(Backtrace::new_unresolved()),
+        BacktraceCapture::Disabled => None,
+    }
+}
+
 #[derive(Debug)]
 pub struct WebDriverError {
     pub error: ErrorStatus,
     pub message: Cow<'static, str>,
-    pub backtrace: Backtrace,
+    pub backtrace: Option<Backtrace>,
+    /// Stacktrace provided by the handler, reported instead of the backtrace
+    /// when using `StacktraceReporting::Provided`.
+    pub stacktrace: Option<String>,
     pub delete_session: bool,
+    /// Additional error information sent to the client, for example the
+    /// prompt text for `unexpected alert open`.
+    pub data: Option<Json>,
+    source: Option<Box<Error + Send + Sync>>,
 }
 
 impl fmt::Display for WebDriverError {
@@ -123,11 +303,101 @@ impl WebDriverError {
         WebDriverError {
             error: error,
             message: message.into(),
-            backtrace: Backtrace::new(),
+            backtrace: capture_backtrace(backtrace_capture()),
+            stacktrace: None,
             delete_session: false,
+            data: None,
+            source: None,
         }
     }
 
+    pub fn with_data(mut self, data: Json) -> WebDriverError {
+        self.data = Some(data);
+        self
+    }
+
+    pub fn with_stacktrace<S: Into<String>>(mut self, stacktrace: S) -> WebDriverError {
+        self.stacktrace = Some(stacktrace.into());
+        self
+    }
+
+    pub fn with_source<E>(mut self, source: E) -> WebDriverError
+        where E: Into<Box<Error + Send + Sync>>
+    {
+        self.source = Some(source.into());
+        self
+    }
+
+    /// Apply a server's `BacktraceCapture` setting to the backtrace of an
+    /// error that may have been created on another thread.
+    pub fn with_backtrace_capture(mut self, capture: BacktraceCapture) -> WebDriverError {
+        match capture {
+            BacktraceCapture::Eager => {
+                if let Some(ref mut backtrace) = self.backtrace {
+                    backtrace.resolve();
+                }
+            },
+            BacktraceCapture::Lazy => {},
+            BacktraceCapture::Disabled => self.backtrace = None,
+        }
+        self
+    }
+
+    /// Decode an error response body sent by a remote end.
+    ///
+    /// This accepts the W3C form, with the `error`, `message`, `stacktrace`
+    /// and `data` fields either at the top level or inside `value`, and the
+    /// legacy form with a numeric `status` and the message inside `value`.
+    /// Error codes that aren't known are kept as `ErrorStatus::Unrecognized`.
+    pub fn from_json(body: &Json) -> WebDriverResult<WebDriverError> {
+        let data = try_opt!(body.as_object(),
+                            ErrorStatus::UnknownError,
+                            "Error response was not an object");
+        let value = data.get("value").and_then(|x| x.as_object());
+
+        let (status, fields) = if let Some(code) = data.get("status") {
+            let code = try_opt!(code.as_u64(),
+                                ErrorStatus::UnknownError,
+                                "Error response status was not an integer");
+            if code == 0 {
+                return Err(WebDriverError::new(ErrorStatus::UnknownError,
+                                               "Response status was success"));
+            }
+            (ErrorStatus::from_legacy_code(code)
+                 .unwrap_or_else(|| ErrorStatus::Unrecognized(code.to_string())),
+             value)
+        } else {
+            let fields = match value {
+                Some(x) if x.contains_key("error") => x,
+                _ => data
+            };
+            let code = try_opt!(fields.get("error").and_then(|x| x.as_string()),
+                                ErrorStatus::UnknownError,
+                                "Error response had no error code");
+            (ErrorStatus::from_code(code)
+                 .unwrap_or_else(|| ErrorStatus::Unrecognized(code.to_owned())),
+             Some(fields))
+        };
+
+        let field = |name| fields.and_then(|x| x.get(name));
+        let message = field("message").and_then(|x| x.as_string()).unwrap_or("").to_owned();
+        Ok(WebDriverError {
+            error: status,
+            message: message.into(),
+            backtrace: None,
+            stacktrace: field("stacktrace")
+                .and_then(|x| x.as_string())
+                .filter(|x| !x.is_empty())
+                .map(|x| x.to_owned()),
+            delete_session: false,
+            data: match field("data") {
+                Some(&Json::Null) | None => None,
+                Some(x) => Some(x.clone())
+            },
+            source: None,
+        })
+    }
+
     pub fn status_code(&self) -> &'static str {
         self.error.status_code()
     }
@@ -139,17 +409,41 @@ impl WebDriverError {
     pub fn to_json_string(&self) -> String {
         self.to_json().to_string()
     }
-}
 
-impl ToJson for WebDriverError {
-    fn to_json(&self) -> Json {
+    /// Serialize the error, reporting its stacktrace as set by `reporting`.
+    ///
+    /// `to_json` uses the default, `StacktraceReporting::Backtrace`.
+    pub fn to_json_with(&self, reporting: StacktraceReporting) -> Json {
         let mut data = BTreeMap::new();
-        data.insert("error".into(), self.status_code().to_json());
+        data.insert("error".into(), self.error.code().to_json());
         data.insert("message".into(), self.message.to_json());
-        data.insert("stacktrace".into(),
-                    format!("{:?}", self.backtrace).to_json());
+        data.insert("stacktrace".into(), self.reported_stacktrace(reporting).to_json());
+        if let Some(ref x) = self.data {
+            data.insert("data".into(), x.clone());
+        }
         Json::Object(data)
     }
+
+    fn reported_stacktrace(&self, reporting: StacktraceReporting) -> String {
+        match reporting {
+            StacktraceReporting::Backtrace => match self.backtrace {
+                Some(ref backtrace) => {
+                    let mut backtrace = backtrace.clone();
+                    backtrace.resolve();
+                    format!("{:?}", backtrace)
+                },
+                None => String::new()
+            },
+            StacktraceReporting::Provided => self.stacktrace.clone().unwrap_or_default(),
+            StacktraceReporting::Redacted => String::new(),
+        }
+    }
+}
+
+impl ToJson for WebDriverError {
+    fn to_json(&self) -> Json {
+        self.to_json_with(StacktraceReporting::default())
+    }
 }
 
 impl Error for WebDriverError {
@@ -157,32 +451,43 @@ impl Error for WebDriverError {
         self.status_code()
     }
 
-    fn cause(&self) -> Option<&Error> {
-        None
+    fn source(&self) -> Option<&(Error + 'static)> {
+        self.source.as_ref().map(|x| &**x as &(Error + 'static))
     }
 }
 
 impl From<ParserError> for WebDriverError {
     fn from(err: ParserError) -> WebDriverError {
         WebDriverError::new(ErrorStatus::UnknownError, err.description().to_string())
+            .with_source(err)
     }
 }
 
 impl From<IoError> for WebDriverError {
     fn from(err: IoError) -> WebDriverError {
         WebDriverError::new(ErrorStatus::UnknownError, err.description().to_string())
+            .with_source(err)
     }
 }
 
 impl From<DecoderError> for WebDriverError {
     fn from(err: DecoderError) -> WebDriverError {
         WebDriverError::new(ErrorStatus::UnknownError, err.description().to_string())
+            .with_source(err)
     }
 }
 
 impl From<FromBase64Error> for WebDriverError {
     fn from(err: FromBase64Error) -> WebDriverError {
         WebDriverError::new(ErrorStatus::UnknownError, err.description().to_string())
+            .with_source(err)
+    }
+}
+
+impl From<HttpError> for WebDriverError {
+    fn from(err: HttpError) -> WebDriverError {
+        WebDriverError::new(ErrorStatus::UnknownError, err.description().to_string())
+            .with_source(err)
     }
 }
 
@@ -191,3 +496,113 @@ impl From<Box<Error>> for WebDriverError {
         WebDriverError::new(ErrorStatus::UnknownError, err.description().to_string())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use rustc_serialize::json::{Json, ToJson};
+    use std::error::Error;
+    use std::io;
+    use super::{backtrace_capture, capture_backtrace, set_backtrace_capture, BacktraceCapture,
+                ErrorStatus, StacktraceReporting, WebDriverError};
+
+    #[test]
+    fn test_from_code() {
+        for status in vec![ErrorStatus::DetachedShadowRoot,
+                           ErrorStatus::ElementClickIntercepted,
+                           ErrorStatus::ElementNotInteractable,
+                           ErrorStatus::NoSuchCookie,
+                           ErrorStatus::NoSuchShadowRoot,
+                           ErrorStatus::UnableToCaptureScreen,
+                           ErrorStatus::UnknownMethod,
+                           ErrorStatus::UnknownPath] {
+            assert_eq!(ErrorStatus::from_code(status.status_code()), Some(status));
+        }
+        assert_eq!(ErrorStatus::from_code("no such thing"), None);
+    }
+
+    #[test]
+    fn test_data_and_source() {
+        let err = WebDriverError::new(ErrorStatus::UnexpectedAlertOpen, "Unexpected alert open")
+            .with_data(Json::from_str(r#"{"text": "hello"}"#).unwrap());
+        let json = err.to_json();
+        assert_eq!(json.find_path(&["data", "text"]), Some(&"hello".to_json()));
+        assert!(err.source().is_none());
+
+        let err = WebDriverError::from(io::Error::new(io::ErrorKind::Other, "disk on fire"));
+        assert_eq!(err.source().unwrap().to_string(), "disk on fire");
+        assert!(err.to_json().find("data").is_none());
+    }
+
+    #[test]
+    fn test_stacktrace() {
+        let mut err = WebDriverError::new(ErrorStatus::JavascriptError, "Error: boom")
+            .with_stacktrace("@https://example.org/app.js:1:1");
+        err.backtrace = capture_backtrace(BacktraceCapture::Lazy);
+        assert!(!err.reported_stacktrace(StacktraceReporting::Backtrace).is_empty());
+        assert_eq!(err.reported_stacktrace(StacktraceReporting::Provided),
+                   "@https://example.org/app.js:1:1");
+        assert_eq!(err.reported_stacktrace(StacktraceReporting::Redacted), "");
+
+        let json = err.to_json_with(StacktraceReporting::Provided);
+        assert_eq!(json.find("stacktrace"), Some(&"@https://example.org/app.js:1:1".to_json()));
+
+        let err = err.with_backtrace_capture(BacktraceCapture::Disabled);
+        assert!(err.backtrace.is_none());
+        assert_eq!(err.reported_stacktrace(StacktraceReporting::Backtrace), "");
+    }
+
+    #[test]
+    fn test_backtrace_capture() {
+        assert_eq!(backtrace_capture(), BacktraceCapture::Eager);
+        assert!(WebDriverError::new(ErrorStatus::UnknownError, "").backtrace.is_some());
+
+        set_backtrace_capture(BacktraceCapture::Disabled);
+        let err = WebDriverError::new(ErrorStatus::UnknownError, "no backtrace");
+        assert!(err.backtrace.is_none());
+        assert_eq!(err.reported_stacktrace(StacktraceReporting::Backtrace), "");
+
+        set_backtrace_capture(BacktraceCapture::Lazy);
+        let err = WebDriverError::new(ErrorStatus::UnknownError, "unresolved");
+        assert!(err.backtrace.is_some());
+        set_backtrace_capture(BacktraceCapture::default());
+    }
+
+    #[test]
+    fn test_from_json() {
+        let body = Json::from_str(r#"{"value": {"error": "no such element", "message": "gone",
+                                                "stacktrace": "@app.js:1:1",
+                                                "data": {"id": 1}}}"#).unwrap();
+        let err = WebDriverError::from_json(&body).unwrap();
+        assert_eq!(err.error, ErrorStatus::NoSuchElement);
+        assert_eq!(err.message, "gone");
+        assert_eq!(err.stacktrace, Some("@app.js:1:1".into()));
+        assert_eq!(err.data, Some(Json::from_str(r#"{"id": 1}"#).unwrap()));
+        assert!(err.backtrace.is_none());
+
+        let err = WebDriverError::new(ErrorStatus::NoSuchCookie, "no cookie");
+        let decoded = WebDriverError::from_json(&err.to_json()).unwrap();
+        assert_eq!(decoded.error, ErrorStatus::NoSuchCookie);
+        assert_eq!(decoded.message, "no cookie");
+
+        let body = Json::from_str(r#"{"value": {"error": "cosmic ray", "message": ""}}"#).unwrap();
+        let err = WebDriverError::from_json(&body).unwrap();
+        assert_eq!(err.error, ErrorStatus::Unrecognized("cosmic ray".into()));
+        assert_eq!(err.status_code(), "unknown error");
+        assert_eq!(err.error.code(), "cosmic ray");
+        assert_eq!(err.to_json().find("error"), Some(&"cosmic ray".to_json()));
+
+        let body = Json::from_str(r#"{"sessionId": "1", "status": 10,
+                                      "value": {"message": "stale"}}"#).unwrap();
+        let err = WebDriverError::from_json(&body).unwrap();
+        assert_eq!(err.error, ErrorStatus::StaleElementReference);
+        assert_eq!(err.message, "stale");
+
+        let body = Json::from_str(r#"{"status": 99, "value": null}"#).unwrap();
+        assert_eq!(WebDriverError::from_json(&body).unwrap().error,
+                   ErrorStatus::Unrecognized("99".into()));
+
+        for body in &[r#"{"status": 0, "value": null}"#, r#"{"value": 1}"#, "[]"] {
+            assert!(WebDriverError::from_json(&Json::from_str(body).unwrap()).is_err());
+        }
+    }
+}
@@ -0,0 +1,521 @@
+//! A higher level API for driving a browser through `client::WebDriverClient`.
+//!
+//! A `Browser` owns a client and its session, and hands out `Element`s that
+//! borrow it, so commands can be written as method calls rather than as
+//! `WebDriverCommand` values.
+
+use std::cell::RefCell;
+use std::cmp;
+use std::time::Duration;
+
+use rustc_serialize::json::{Json, ToJson};
+
+use client::WebDriverClient;
+use command::{GetParameters, JavascriptCommandParameters, LocatorParameters,
+              NewWindowParameters, Parameters, SendKeysParameters,
+              SwitchToFrameParameters, SwitchToWindowParameters, WebDriverCommand};
+use common::{Clock, FrameId, LocatorStrategy, Nullable, SystemClock, WebElement, WindowType};
+use error::{ErrorStatus, WebDriverError, WebDriverResult};
+use httpapi::{WebDriverExtensionRoute, VoidWebDriverExtensionRoute};
+use response::WebDriverResponse;
+
+/// An explicit wait, polling a condition until it holds or times out.
+pub struct Wait {
+    timeout: Duration,
+    poll_interval: Duration,
+    ignored: Vec<ErrorStatus>,
+    clock: Box<Clock>,
+}
+
+impl Wait {
+    /// Create a wait with a `timeout`, polling every 100ms.
+    pub fn new(timeout: Duration) -> Wait {
+        Wait {
+            timeout: timeout,
+            poll_interval: Duration::from_millis(100),
+            ignored: vec![],
+            clock: Box::new(SystemClock),
+        }
+    }
+
+    pub fn poll_interval(mut self, poll_interval: Duration) -> Wait {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// Treat errors of kind `error` as the condition not holding yet.
+    pub fn ignoring(mut self, error: ErrorStatus) -> Wait {
+        self.ignored.push(error);
+        self
+    }
+
+    pub fn clock(mut self, clock: Box<Clock>) -> Wait {
+        self.clock = clock;
+        self
+    }
+
+    /// Call `condition` until it returns a value, returning that value.
+    ///
+    /// The condition is always called at least once. Errors that aren't
+    /// ignored are returned immediately. On timeout, the error is a
+    /// `Timeout` whose source is the last ignored error, if any.
+    pub fn until<F, T>(&self, mut condition: F) -> WebDriverResult<T>
+        where F: FnMut() -> WebDriverResult<Option<T>>
+    {
+        let deadline = self.clock.now() + self.timeout;
+        loop {
+            let last_error = match condition() {
+                Ok(Some(x)) => return Ok(x),
+                Ok(None) => None,
+                Err(err) => {
+                    if !self.ignored.contains(&err.error) {
+                        return Err(err);
+                    }
+                    Some(err)
+                }
+            };
+            let now = self.clock.now();
+            if now >= deadline {
+                let err = WebDriverError::new(ErrorStatus::Timeout,
+                                              "Timed out waiting for condition");
+                return Err(match last_error {
+                    Some(source) => err.with_source(source),
+                    None => err
+                });
+            }
+            self.clock.sleep(cmp::min(self.poll_interval, deadline - now));
+        }
+    }
+}
+
+/// A browser session.
+///
+/// The session is created by `Browser::new_session` or attached to the
+/// client beforehand, and ended by `quit`.
+pub struct Browser<U: WebDriverExtensionRoute=VoidWebDriverExtensionRoute> {
+    client: RefCell<WebDriverClient<U>>,
+}
+
+impl <U: WebDriverExtensionRoute> Browser<U> {
+    pub fn new(client: WebDriverClient<U>) -> Browser<U> {
+        Browser {
+            client: RefCell::new(client)
+        }
+    }
+
+    /// Start a session; `parameters` is the body of the `NewSession` command,
+    /// e.g. `{"capabilities": {"alwaysMatch": {"browserName": "firefox"}}}`.
+    pub fn new_session(client: WebDriverClient<U>, parameters: &Json) -> WebDriverResult<Browser<U>> {
+        let browser = Browser::new(client);
+        try!(browser.send(WebDriverCommand::NewSession(try!(Parameters::from_json(parameters)))));
+        Ok(browser)
+    }
+
+    pub fn session_id(&self) -> Option<String> {
+        self.client.borrow().session_id().map(|x| x.to_owned())
+    }
+
+    pub fn send(&self, command: WebDriverCommand<U::Command>) -> WebDriverResult<WebDriverResponse> {
+        self.client.borrow_mut().send(command)
+    }
+
+    fn send_value(&self, command: WebDriverCommand<U::Command>) -> WebDriverResult<Json> {
+        response_value(try!(self.send(command)))
+    }
+
+    /// End the session.
+    pub fn quit(self) -> WebDriverResult<()> {
+        try!(self.send(WebDriverCommand::DeleteSession));
+        Ok(())
+    }
+
+    pub fn goto(&self, url: &str) -> WebDriverResult<()> {
+        try!(self.send(WebDriverCommand::Get(GetParameters { url: url.into() })));
+        Ok(())
+    }
+
+    pub fn current_url(&self) -> WebDriverResult<String> {
+        json_string(try!(self.send_value(WebDriverCommand::GetCurrentUrl)))
+    }
+
+    pub fn title(&self) -> WebDriverResult<String> {
+        json_string(try!(self.send_value(WebDriverCommand::GetTitle)))
+    }
+
+    pub fn find(&self, using: LocatorStrategy, value: &str) -> WebDriverResult<Element<U>> {
+        let locator = LocatorParameters { using: using, value: value.into() };
+        self.element(try!(self.send_value(WebDriverCommand::FindElement(locator))))
+    }
+
+    pub fn find_all(&self, using: LocatorStrategy, value: &str) -> WebDriverResult<Vec<Element<U>>> {
+        let locator = LocatorParameters { using: using, value: value.into() };
+        self.elements(try!(self.send_value(WebDriverCommand::FindElements(locator))))
+    }
+
+    pub fn active_element(&self) -> WebDriverResult<Element<U>> {
+        self.element(try!(self.send_value(WebDriverCommand::GetActiveElement)))
+    }
+
+    /// Run a synchronous script, returning its result.
+    pub fn execute(&self, script: &str, args: Vec<Json>) -> WebDriverResult<Json> {
+        let parameters = JavascriptCommandParameters {
+            script: script.into(),
+            args: Nullable::Value(args),
+        };
+        self.send_value(WebDriverCommand::ExecuteScript(parameters))
+    }
+
+    /// Wait until `condition` returns a value, as described by `wait`.
+    pub fn wait_until<F, T>(&self, wait: &Wait, mut condition: F) -> WebDriverResult<T>
+        where F: FnMut(&Browser<U>) -> WebDriverResult<Option<T>>
+    {
+        wait.until(|| condition(self))
+    }
+
+    /// Wait for an element to be present.
+    pub fn wait_for(&self, wait: &Wait, using: LocatorStrategy, value: &str)
+                    -> WebDriverResult<Element<U>> {
+        wait.until(|| self.find(using.clone(), value).map(Some))
+    }
+
+    pub fn window_handle(&self) -> WebDriverResult<String> {
+        json_string(try!(self.send_value(WebDriverCommand::GetWindowHandle)))
+    }
+
+    pub fn window_handles(&self) -> WebDriverResult<Vec<String>> {
+        let value = try!(self.send_value(WebDriverCommand::GetWindowHandles));
+        let handles = try_opt!(value.as_array(),
+                               ErrorStatus::UnknownError,
+                               "Expected an array of window handles");
+        handles.iter().map(|x| json_string(x.clone())).collect()
+    }
+
+    /// Open a new window, returning its handle. The current window is
+    /// unchanged.
+    pub fn new_window(&self, type_hint: Option<WindowType>) -> WebDriverResult<String> {
+        let parameters = NewWindowParameters { type_hint: type_hint };
+        match try!(self.send(WebDriverCommand::NewWindow(parameters))) {
+            WebDriverResponse::NewWindow(x) => Ok(x.handle),
+            x => Err(unexpected_response(x))
+        }
+    }
+
+    /// Close the current window, returning the handles of those remaining.
+    pub fn close_window(&self) -> WebDriverResult<Vec<String>> {
+        match try!(self.send(WebDriverCommand::CloseWindow)) {
+            WebDriverResponse::CloseWindow(x) => Ok(x.window_handles),
+            x => Err(unexpected_response(x))
+        }
+    }
+
+    pub fn switch_to_window(&self, handle: &str) -> WebDriverResult<()> {
+        let parameters = SwitchToWindowParameters { handle: handle.into() };
+        try!(self.send(WebDriverCommand::SwitchToWindow(parameters)));
+        Ok(())
+    }
+
+    /// Switch to the frame at `index` in the current browsing context.
+    pub fn switch_to_frame(&self, index: u16) -> WebDriverResult<()> {
+        self.switch_to(FrameId::Short(index))
+    }
+
+    /// Switch to the frame contained in an `iframe` or `frame` element.
+    pub fn switch_to_frame_element(&self, element: &Element<U>) -> WebDriverResult<()> {
+        self.switch_to(FrameId::Element(element.element.clone()))
+    }
+
+    /// Switch to the top-level browsing context.
+    pub fn switch_to_top(&self) -> WebDriverResult<()> {
+        self.switch_to(FrameId::Null)
+    }
+
+    pub fn switch_to_parent_frame(&self) -> WebDriverResult<()> {
+        try!(self.send(WebDriverCommand::SwitchToParentFrame));
+        Ok(())
+    }
+
+    fn switch_to(&self, id: FrameId) -> WebDriverResult<()> {
+        try!(self.send(WebDriverCommand::SwitchToFrame(SwitchToFrameParameters { id: id })));
+        Ok(())
+    }
+
+    fn element(&self, value: Json) -> WebDriverResult<Element<U>> {
+        Ok(Element {
+            browser: self,
+            element: try!(WebElement::from_json(&value)),
+        })
+    }
+
+    fn elements(&self, value: Json) -> WebDriverResult<Vec<Element<U>>> {
+        let elements = try_opt!(value.as_array(),
+                                ErrorStatus::UnknownError,
+                                "Expected an array of elements");
+        elements.iter().map(|x| self.element(x.clone())).collect()
+    }
+}
+
+/// An element in the current browsing context of a `Browser`.
+pub struct Element<'a, U: 'a + WebDriverExtensionRoute=VoidWebDriverExtensionRoute> {
+    browser: &'a Browser<U>,
+    element: WebElement,
+}
+
+impl <'a, U: WebDriverExtensionRoute> Element<'a, U> {
+    pub fn web_element(&self) -> &WebElement {
+        &self.element
+    }
+
+    fn send_value(&self, command: WebDriverCommand<U::Command>) -> WebDriverResult<Json> {
+        self.browser.send_value(command)
+    }
+
+    /// Find an element within this one.
+    pub fn find(&self, using: LocatorStrategy, value: &str) -> WebDriverResult<Element<'a, U>> {
+        let locator = LocatorParameters { using: using, value: value.into() };
+        let value = try!(self.send_value(
+            WebDriverCommand::FindElementElement(self.element.clone(), locator)));
+        self.browser.element(value)
+    }
+
+    pub fn find_all(&self, using: LocatorStrategy, value: &str) -> WebDriverResult<Vec<Element<'a, U>>> {
+        let locator = LocatorParameters { using: using, value: value.into() };
+        let value = try!(self.send_value(
+            WebDriverCommand::FindElementElements(self.element.clone(), locator)));
+        self.browser.elements(value)
+    }
+
+    pub fn click(&self) -> WebDriverResult<()> {
+        try!(self.browser.send(WebDriverCommand::ElementClick(self.element.clone())));
+        Ok(())
+    }
+
+    pub fn clear(&self) -> WebDriverResult<()> {
+        try!(self.browser.send(WebDriverCommand::ElementClear(self.element.clone())));
+        Ok(())
+    }
+
+    pub fn send_keys(&self, text: &str) -> WebDriverResult<()> {
+        let parameters = SendKeysParameters { value: text.chars().collect() };
+        try!(self.browser.send(WebDriverCommand::ElementSendKeys(self.element.clone(), parameters)));
+        Ok(())
+    }
+
+    pub fn text(&self) -> WebDriverResult<String> {
+        json_string(try!(self.send_value(WebDriverCommand::GetElementText(self.element.clone()))))
+    }
+
+    pub fn tag_name(&self) -> WebDriverResult<String> {
+        json_string(try!(self.send_value(WebDriverCommand::GetElementTagName(self.element.clone()))))
+    }
+
+    /// Get an attribute, or `None` if the element doesn't have it.
+    pub fn attr(&self, name: &str) -> WebDriverResult<Option<String>> {
+        let value = try!(self.send_value(
+            WebDriverCommand::GetElementAttribute(self.element.clone(), name.into())));
+        match value {
+            Json::Null => Ok(None),
+            x => json_string(x).map(Some)
+        }
+    }
+
+    pub fn property(&self, name: &str) -> WebDriverResult<Json> {
+        self.send_value(WebDriverCommand::GetElementProperty(self.element.clone(), name.into()))
+    }
+
+    pub fn css_value(&self, name: &str) -> WebDriverResult<String> {
+        json_string(try!(self.send_value(
+            WebDriverCommand::GetCSSValue(self.element.clone(), name.into()))))
+    }
+
+    pub fn is_displayed(&self) -> WebDriverResult<bool> {
+        json_bool(try!(self.send_value(WebDriverCommand::IsDisplayed(self.element.clone()))))
+    }
+
+    pub fn is_enabled(&self) -> WebDriverResult<bool> {
+        json_bool(try!(self.send_value(WebDriverCommand::IsEnabled(self.element.clone()))))
+    }
+
+    pub fn is_selected(&self) -> WebDriverResult<bool> {
+        json_bool(try!(self.send_value(WebDriverCommand::IsSelected(self.element.clone()))))
+    }
+}
+
+impl <'a, U: WebDriverExtensionRoute> ToJson for Element<'a, U> {
+    fn to_json(&self) -> Json {
+        self.element.to_json()
+    }
+}
+
+fn unexpected_response(response: WebDriverResponse) -> WebDriverError {
+    WebDriverError::new(ErrorStatus::UnknownError,
+                        format!("Unexpected response {:?}", response))
+}
+
+fn response_value(response: WebDriverResponse) -> WebDriverResult<Json> {
+    match response {
+        WebDriverResponse::Generic(x) => Ok(x.value),
+        WebDriverResponse::Void => Ok(Json::Null),
+        x => Err(unexpected_response(x))
+    }
+}
+
+fn json_string(value: Json) -> WebDriverResult<String> {
+    match value {
+        Json::String(x) => Ok(x),
+        x => Err(WebDriverError::new(ErrorStatus::UnknownError,
+                                     format!("Expected a string, got {}", x)))
+    }
+}
+
+fn json_bool(value: Json) -> WebDriverResult<bool> {
+    Ok(try_opt!(value.as_boolean(),
+                ErrorStatus::UnknownError,
+                format!("Expected a boolean, got {}", value)))
+}
+
+#[cfg(test)]
+mod tests {
+    use client::WebDriverClient;
+    use command::{WebDriverCommand, WebDriverMessage};
+    use common::{Clock, FrameId, LocatorStrategy, WebElement};
+    use error::{ErrorStatus, WebDriverError, WebDriverResult};
+    use response::{NewSessionResponse, ValueResponse, WebDriverResponse};
+    use rustc_serialize::json::{Json, ToJson};
+    use server::{Session, TestServer, WebDriverHandler};
+    use std::error::Error;
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+    use super::{Browser, Wait};
+
+    struct TestHandler {
+        find_calls: u32,
+        frame: Option<String>,
+    }
+
+    impl WebDriverHandler for TestHandler {
+        fn handle_command(&mut self, _: &Option<Session>,
+                          msg: WebDriverMessage) -> WebDriverResult<WebDriverResponse> {
+            let value = match msg.command {
+                WebDriverCommand::NewSession(_) => {
+                    return Ok(WebDriverResponse::NewSession(NewSessionResponse::new(
+                        "session-1".into(), Json::from_str("{}").unwrap())))
+                },
+                WebDriverCommand::DeleteSession => return Ok(WebDriverResponse::DeleteSession),
+                WebDriverCommand::FindElement(ref x) if x.value == "#late" => {
+                    self.find_calls += 1;
+                    if self.find_calls < 3 {
+                        return Err(WebDriverError::new(ErrorStatus::NoSuchElement, "Not yet"));
+                    }
+                    WebElement::new("late".into()).to_json()
+                },
+                WebDriverCommand::FindElements(_) => {
+                    vec![WebElement::new("a".into()), WebElement::new("b".into())].to_json()
+                },
+                WebDriverCommand::GetElementText(ref x) => format!("text of {}", x.id).to_json(),
+                WebDriverCommand::GetElementAttribute(_, ref name) if name == "href" => {
+                    "/next".to_json()
+                },
+                WebDriverCommand::GetElementAttribute(..) => Json::Null,
+                WebDriverCommand::GetWindowHandles => vec!["w1".to_string(), "w2".into()].to_json(),
+                WebDriverCommand::SwitchToFrame(ref x) => {
+                    self.frame = match x.id {
+                        FrameId::Element(ref x) => Some(x.id.clone()),
+                        _ => None
+                    };
+                    return Ok(WebDriverResponse::Void)
+                },
+                WebDriverCommand::ExecuteScript(_) => self.frame.to_json(),
+                _ => return Err(WebDriverError::new(ErrorStatus::NoSuchElement, "Nothing here")),
+            };
+            Ok(WebDriverResponse::Generic(ValueResponse::new(value)))
+        }
+
+        fn delete_session(&mut self, _: &Option<Session>) {}
+    }
+
+    fn start_browser() -> (TestServer, Browser) {
+        let server = TestServer::start(TestHandler { find_calls: 0, frame: None });
+        let client = WebDriverClient::new(&server.url(), &[]);
+        let browser = Browser::new_session(client, &Json::from_str("{}").unwrap()).unwrap();
+        (server, browser)
+    }
+
+    fn short_wait() -> Wait {
+        Wait::new(Duration::from_millis(200)).poll_interval(Duration::from_millis(1))
+    }
+
+    #[test]
+    fn test_elements() {
+        let (_server, browser) = start_browser();
+        assert_eq!(browser.session_id(), Some("session-1".into()));
+
+        let elements = browser.find_all(LocatorStrategy::CSSSelector, "a").unwrap();
+        assert_eq!(elements.len(), 2);
+        assert_eq!(elements[1].text().unwrap(), "text of b");
+        assert_eq!(elements[0].attr("href").unwrap(), Some("/next".into()));
+        assert_eq!(elements[0].attr("title").unwrap(), None);
+
+        browser.switch_to_frame_element(&elements[1]).unwrap();
+        assert_eq!(browser.execute("return frame", vec![]).unwrap(), "b".to_json());
+        browser.switch_to_top().unwrap();
+        assert_eq!(browser.execute("return frame", vec![]).unwrap(), Json::Null);
+
+        assert_eq!(browser.window_handles().unwrap(), vec!["w1", "w2"]);
+        browser.quit().unwrap();
+    }
+
+    #[test]
+    fn test_wait() {
+        let (_server, browser) = start_browser();
+
+        let err = browser.wait_for(&short_wait(), LocatorStrategy::CSSSelector, "#late")
+            .err().unwrap();
+        assert_eq!(err.error, ErrorStatus::NoSuchElement);
+
+        let wait = short_wait().ignoring(ErrorStatus::NoSuchElement);
+        let element = browser.wait_for(&wait, LocatorStrategy::CSSSelector, "#late").unwrap();
+        assert_eq!(element.web_element().id, "late");
+
+        let err = browser.wait_for(&wait, LocatorStrategy::CSSSelector, "#missing")
+            .err().unwrap();
+        assert_eq!(err.error, ErrorStatus::Timeout);
+        assert_eq!(err.source().unwrap().to_string(), "Nothing here");
+
+        let mut polls = 0;
+        let title = browser.wait_until(&wait, |_| {
+            polls += 1;
+            Ok(if polls == 3 { Some("ready") } else { None })
+        }).unwrap();
+        assert_eq!((title, polls), ("ready", 3));
+    }
+
+    struct FakeClock {
+        start: Instant,
+        sleeps: Arc<Mutex<Vec<Duration>>>,
+    }
+
+    impl Clock for FakeClock {
+        fn now(&self) -> Instant {
+            self.start + self.sleeps.lock().unwrap().iter().sum::<Duration>()
+        }
+
+        fn sleep(&self, duration: Duration) {
+            self.sleeps.lock().unwrap().push(duration);
+        }
+    }
+
+    #[test]
+    fn test_wait_deadline() {
+        let sleeps = Arc::new(Mutex::new(vec![]));
+        let clock = FakeClock { start: Instant::now(), sleeps: sleeps.clone() };
+        let wait = Wait::new(Duration::from_millis(250))
+            .poll_interval(Duration::from_millis(100))
+            .clock(Box::new(clock));
+
+        let err = wait.until(|| Ok(None as Option<()>)).err().unwrap();
+        assert_eq!(err.error, ErrorStatus::Timeout);
+        assert_eq!(*sleeps.lock().unwrap(),
+                   vec![Duration::from_millis(100), Duration::from_millis(100),
+                        Duration::from_millis(50)]);
+    }
+}
@@ -1,11 +1,13 @@
 use regex::{Regex, Captures};
-use rustc_serialize::json::Json;
+use rustc_serialize::json::{Json, ToJson};
+use std::collections::BTreeMap;
 
 use hyper::method::Method;
 use hyper::method::Method::{Get, Post, Delete};
 
 use command::{WebDriverCommand, WebDriverMessage, WebDriverExtensionCommand,
               VoidWebDriverExtensionCommand};
+use common::{FrameId, WebElement};
 use error::{WebDriverResult, WebDriverError, ErrorStatus};
 
 fn standard_routes<U:WebDriverExtensionRoute>() -> Vec<(Method, &'static str, Route<U>)> {
@@ -25,8 +27,13 @@ fn standard_routes<U:WebDriverExtensionRoute>() -> Vec<(Method, &'static str, Ro
                 (Post, "/session/{sessionId}/window/size", Route::SetWindowSize),
                 (Get, "/session/{sessionId}/window/position", Route::GetWindowPosition),
                 (Post, "/session/{sessionId}/window/position", Route::SetWindowPosition),
+                (Get, "/session/{sessionId}/window/rect", Route::GetWindowRect),
+                (Post, "/session/{sessionId}/window/rect", Route::SetWindowRect),
                 (Post, "/session/{sessionId}/window/maximize", Route::MaximizeWindow),
+                (Post, "/session/{sessionId}/window/minimize", Route::MinimizeWindow),
+                (Post, "/session/{sessionId}/window/fullscreen", Route::FullscreenWindow),
                 (Post, "/session/{sessionId}/window", Route::SwitchToWindow),
+                (Post, "/session/{sessionId}/window/new", Route::NewWindow),
                 (Post, "/session/{sessionId}/frame", Route::SwitchToFrame),
                 (Post, "/session/{sessionId}/frame/parent", Route::SwitchToParentFrame),
                 (Post, "/session/{sessionId}/element", Route::FindElement),
@@ -34,10 +41,15 @@ fn standard_routes<U:WebDriverExtensionRoute>() -> Vec<(Method, &'static str, Ro
                 (Post, "/session/{sessionId}/element/{elementId}/element", Route::FindElementElement),
                 (Post, "/session/{sessionId}/element/{elementId}/elements", Route::FindElementElements),
                 (Get, "/session/{sessionId}/element/active", Route::GetActiveElement),
+                (Get, "/session/{sessionId}/element/{elementId}/shadow", Route::GetShadowRoot),
+                (Post, "/session/{sessionId}/shadow/{shadowId}/element", Route::FindShadowRootElement),
+                (Post, "/session/{sessionId}/shadow/{shadowId}/elements", Route::FindShadowRootElements),
                 (Get, "/session/{sessionId}/element/{elementId}/displayed", Route::IsDisplayed),
                 (Get, "/session/{sessionId}/element/{elementId}/selected", Route::IsSelected),
                 (Get, "/session/{sessionId}/element/{elementId}/attribute/{name}", Route::GetElementAttribute),
                 (Get, "/session/{sessionId}/element/{elementId}/property/{name}", Route::GetElementProperty),
+                (Get, "/session/{sessionId}/element/{elementId}/computedrole", Route::GetComputedRole),
+                (Get, "/session/{sessionId}/element/{elementId}/computedlabel", Route::GetComputedLabel),
                 (Get, "/session/{sessionId}/element/{elementId}/css/{propertyName}", Route::GetCSSValue),
                 (Get, "/session/{sessionId}/element/{elementId}/text", Route::GetElementText),
                 (Get, "/session/{sessionId}/element/{elementId}/name", Route::GetElementTagName),
@@ -63,6 +75,15 @@ fn standard_routes<U:WebDriverExtensionRoute>() -> Vec<(Method, &'static str, Ro
                 (Post, "/session/{sessionId}/alert/text", Route::SendAlertText),
                 (Get, "/session/{sessionId}/screenshot", Route::TakeScreenshot),
                 (Get, "/session/{sessionId}/element/{elementId}/screenshot", Route::TakeElementScreenshot),
+                (Post, "/session/{sessionId}/print", Route::Print),
+                (Post, "/session/{sessionId}/permissions", Route::SetPermission),
+                (Post, "/session/{sessionId}/webauthn/authenticator", Route::AddVirtualAuthenticator),
+                (Delete, "/session/{sessionId}/webauthn/authenticator/{authenticatorId}", Route::RemoveVirtualAuthenticator),
+                (Post, "/session/{sessionId}/webauthn/authenticator/{authenticatorId}/credential", Route::AddCredential),
+                (Get, "/session/{sessionId}/webauthn/authenticator/{authenticatorId}/credentials", Route::GetCredentials),
+                (Delete, "/session/{sessionId}/webauthn/authenticator/{authenticatorId}/credentials/{credentialId}", Route::RemoveCredential),
+                (Delete, "/session/{sessionId}/webauthn/authenticator/{authenticatorId}/credentials", Route::RemoveAllCredentials),
+                (Post, "/session/{sessionId}/webauthn/authenticator/{authenticatorId}/uv", Route::SetUserVerified),
                 (Post, "/session/{sessionId}/actions", Route::PerformActions),
                 (Delete, "/session/{sessionId}/actions", Route::ReleaseActions),
                 // TODO Remove this when > v0.5 is released. There for compatibility reasons with existing
@@ -79,7 +100,7 @@ fn standard_routes<U:WebDriverExtensionRoute>() -> Vec<(Method, &'static str, Ro
                 (Get, "/status", Route::Status),]
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq)]
 pub enum Route<U:WebDriverExtensionRoute> {
     NewSession,
     DeleteSession,
@@ -93,11 +114,16 @@ pub enum Route<U:WebDriverExtensionRoute> {
     GetWindowHandle,
     GetWindowHandles,
     CloseWindow,
+    NewWindow,
     GetWindowSize,
     SetWindowSize,
     GetWindowPosition,
     SetWindowPosition,
+    GetWindowRect,
+    SetWindowRect,
     MaximizeWindow,
+    MinimizeWindow,
+    FullscreenWindow,
     SwitchToWindow,
     SwitchToFrame,
     SwitchToParentFrame,
@@ -106,10 +132,15 @@ pub enum Route<U:WebDriverExtensionRoute> {
     FindElementElement,
     FindElementElements,
     GetActiveElement,
+    GetShadowRoot,
+    FindShadowRootElement,
+    FindShadowRootElements,
     IsDisplayed,
     IsSelected,
     GetElementAttribute,
     GetElementProperty,
+    GetComputedRole,
+    GetComputedLabel,
     GetCSSValue,
     GetElementText,
     GetElementTagName,
@@ -136,6 +167,15 @@ pub enum Route<U:WebDriverExtensionRoute> {
     SendAlertText,
     TakeScreenshot,
     TakeElementScreenshot,
+    Print,
+    SetPermission,
+    AddVirtualAuthenticator,
+    RemoveVirtualAuthenticator,
+    AddCredential,
+    GetCredentials,
+    RemoveCredential,
+    RemoveAllCredentials,
+    SetUserVerified,
     Status,
     Extension(U),
 }
@@ -144,6 +184,16 @@ pub trait WebDriverExtensionRoute : Clone + Send + PartialEq {
     type Command: WebDriverExtensionCommand + 'static;
 
     fn command(&self, &Captures, &Json) -> WebDriverResult<WebDriverCommand<Self::Command>>;
+
+    /// Find the route of an extension command, together with the values of
+    /// its path parameters other than `sessionId`.
+    ///
+    /// This is the inverse of `command`, used when encoding extension
+    /// commands as HTTP requests.
+    fn route(_: &Self::Command) -> WebDriverResult<(Self, Vec<(&'static str, String)>)> {
+        Err(WebDriverError::new(ErrorStatus::UnsupportedOperation,
+                                "Encoding extension commands is not supported"))
+    }
 }
 
 #[derive(Clone, PartialEq)]
@@ -157,9 +207,49 @@ impl WebDriverExtensionRoute for VoidWebDriverExtensionRoute {
     }
 }
 
+/// Percent-encode a value for use as a single path segment.
+///
+/// Everything except the unreserved characters of RFC 3986 is encoded, so
+/// values may contain `/`, `?`, `#` and `%`.
+pub fn encode_path_segment(value: &str) -> String {
+    let mut rv = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+            rv.push(byte as char)
+        } else {
+            rv.push_str(&format!("%{:02X}", byte))
+        }
+    }
+    rv
+}
+
+/// Decode a percent-encoded path segment.
+///
+/// A `%` not followed by two hex digits is kept as is.
+pub fn decode_path_segment(segment: &str) -> String {
+    let bytes = segment.as_bytes();
+    let mut rv = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let high = (bytes[i + 1] as char).to_digit(16);
+            let low = (bytes[i + 2] as char).to_digit(16);
+            if let (Some(high), Some(low)) = (high, low) {
+                rv.push((high * 16 + low) as u8);
+                i += 3;
+                continue;
+            }
+        }
+        rv.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&rv).into_owned()
+}
+
 #[derive(Clone)]
 struct RequestMatcher<U: WebDriverExtensionRoute> {
     method: Method,
+    path: String,
     path_regexp: Regex,
     match_type: Route<U>
 }
@@ -169,6 +259,7 @@ impl <U: WebDriverExtensionRoute> RequestMatcher<U> {
         let path_regexp = RequestMatcher::<U>::compile_path(path);
         RequestMatcher {
             method: method,
+            path: path.to_owned(),
             path_regexp: path_regexp,
             match_type: match_type
         }
@@ -179,6 +270,23 @@ impl <U: WebDriverExtensionRoute> RequestMatcher<U> {
         (method == self.method, captures)
     }
 
+    /// Fill in the parameters of the path pattern, percent-encoding their
+    /// values.
+    pub fn build_path(&self, params: &[(&str, String)]) -> WebDriverResult<String> {
+        let components = try!(self.path.split('/').map(|component| {
+            if component.starts_with("{") {
+                let name = &component[1..component.len()-1];
+                let value = try_opt!(params.iter().find(|&&(key, _)| key == name),
+                                     ErrorStatus::InvalidArgument,
+                                     format!("Missing {} parameter", name));
+                Ok(encode_path_segment(&value.1))
+            } else {
+                Ok(component.to_owned())
+            }
+        }).collect::<WebDriverResult<Vec<String>>>());
+        Ok(components.join("/"))
+    }
+
     fn compile_path(path: &str) -> Regex {
         let mut rv = String::new();
         rv.push_str("^");
@@ -245,4 +353,339 @@ impl <U: WebDriverExtensionRoute> WebDriverHttpApi<U> {
         Err(WebDriverError::new(error,
                                 format!("{} {} did not match a known command", method, path)))
     }
+
+    /// Encode a message as the method, path and body of an HTTP request.
+    ///
+    /// This is the inverse of `decode_request`. Commands with several routes
+    /// use the first one, so the W3C route is preferred over legacy aliases.
+    pub fn encode_request(&self, msg: &WebDriverMessage<U>)
+                          -> WebDriverResult<(Method, String, Option<String>)> {
+        let (route, mut params) = try!(command_route::<U>(&msg.command));
+        if let Some(ref session_id) = msg.session_id {
+            params.push(("sessionId", session_id.clone()));
+        }
+
+        let &(ref method, ref matcher) = try_opt!(
+            self.routes.iter().find(|&&(_, ref matcher)| matcher.match_type == route),
+            ErrorStatus::UnknownPath,
+            "Command has no registered route");
+        if msg.session_id.is_none() && matcher.path.contains("{sessionId}") {
+            return Err(WebDriverError::new(ErrorStatus::InvalidSessionId,
+                                           "Command requires a session"));
+        }
+        let path = try!(matcher.build_path(&params));
+
+        let body = if *method == Post {
+            Some(request_body(msg).to_string())
+        } else {
+            None
+        };
+        Ok((method.clone(), path, body))
+    }
+}
+
+fn command_route<U: WebDriverExtensionRoute>(command: &WebDriverCommand<U::Command>)
+                                             -> WebDriverResult<(Route<U>, Vec<(&'static str, String)>)> {
+    let element_id = |element: &WebElement| vec![("elementId", element.id.clone())];
+
+    Ok(match *command {
+        WebDriverCommand::NewSession(_) => (Route::NewSession, vec![]),
+        WebDriverCommand::DeleteSession => (Route::DeleteSession, vec![]),
+        WebDriverCommand::Get(_) => (Route::Get, vec![]),
+        WebDriverCommand::GetCurrentUrl => (Route::GetCurrentUrl, vec![]),
+        WebDriverCommand::GoBack => (Route::GoBack, vec![]),
+        WebDriverCommand::GoForward => (Route::GoForward, vec![]),
+        WebDriverCommand::Refresh => (Route::Refresh, vec![]),
+        WebDriverCommand::GetTitle => (Route::GetTitle, vec![]),
+        WebDriverCommand::GetPageSource => (Route::GetPageSource, vec![]),
+        WebDriverCommand::GetWindowHandle => (Route::GetWindowHandle, vec![]),
+        WebDriverCommand::GetWindowHandles => (Route::GetWindowHandles, vec![]),
+        WebDriverCommand::CloseWindow => (Route::CloseWindow, vec![]),
+        WebDriverCommand::NewWindow(_) => (Route::NewWindow, vec![]),
+        WebDriverCommand::GetWindowSize => (Route::GetWindowSize, vec![]),
+        WebDriverCommand::SetWindowSize(_) => (Route::SetWindowSize, vec![]),
+        WebDriverCommand::GetWindowPosition => (Route::GetWindowPosition, vec![]),
+        WebDriverCommand::SetWindowPosition(_) => (Route::SetWindowPosition, vec![]),
+        WebDriverCommand::GetWindowRect => (Route::GetWindowRect, vec![]),
+        WebDriverCommand::SetWindowRect(_) => (Route::SetWindowRect, vec![]),
+        WebDriverCommand::MaximizeWindow => (Route::MaximizeWindow, vec![]),
+        WebDriverCommand::MinimizeWindow => (Route::MinimizeWindow, vec![]),
+        WebDriverCommand::FullscreenWindow => (Route::FullscreenWindow, vec![]),
+        WebDriverCommand::SwitchToWindow(_) => (Route::SwitchToWindow, vec![]),
+        WebDriverCommand::SwitchToFrame(_) => (Route::SwitchToFrame, vec![]),
+        WebDriverCommand::SwitchToParentFrame => (Route::SwitchToParentFrame, vec![]),
+        WebDriverCommand::FindElement(_) => (Route::FindElement, vec![]),
+        WebDriverCommand::FindElements(_) => (Route::FindElements, vec![]),
+        WebDriverCommand::GetActiveElement => (Route::GetActiveElement, vec![]),
+        WebDriverCommand::ExecuteScript(_) => (Route::ExecuteScript, vec![]),
+        WebDriverCommand::ExecuteAsyncScript(_) => (Route::ExecuteAsyncScript, vec![]),
+        WebDriverCommand::GetCookies => (Route::GetCookies, vec![]),
+        WebDriverCommand::AddCookie(_) => (Route::AddCookie, vec![]),
+        WebDriverCommand::DeleteCookies => (Route::DeleteCookies, vec![]),
+        WebDriverCommand::GetTimeouts => (Route::GetTimeouts, vec![]),
+        WebDriverCommand::SetTimeouts(_) => (Route::SetTimeouts, vec![]),
+        WebDriverCommand::PerformActions(_) => (Route::PerformActions, vec![]),
+        WebDriverCommand::ReleaseActions => (Route::ReleaseActions, vec![]),
+        WebDriverCommand::DismissAlert => (Route::DismissAlert, vec![]),
+        WebDriverCommand::AcceptAlert => (Route::AcceptAlert, vec![]),
+        WebDriverCommand::GetAlertText => (Route::GetAlertText, vec![]),
+        WebDriverCommand::SendAlertText(_) => (Route::SendAlertText, vec![]),
+        WebDriverCommand::TakeScreenshot => (Route::TakeScreenshot, vec![]),
+        WebDriverCommand::Print(_) => (Route::Print, vec![]),
+        WebDriverCommand::SetPermission(_) => (Route::SetPermission, vec![]),
+        WebDriverCommand::AddVirtualAuthenticator(_) => (Route::AddVirtualAuthenticator, vec![]),
+        WebDriverCommand::Status => (Route::Status, vec![]),
+
+        WebDriverCommand::FindElementElement(ref x, _) => (Route::FindElementElement, element_id(x)),
+        WebDriverCommand::FindElementElements(ref x, _) => (Route::FindElementElements, element_id(x)),
+        WebDriverCommand::GetShadowRoot(ref x) => (Route::GetShadowRoot, element_id(x)),
+        WebDriverCommand::IsDisplayed(ref x) => (Route::IsDisplayed, element_id(x)),
+        WebDriverCommand::IsSelected(ref x) => (Route::IsSelected, element_id(x)),
+        WebDriverCommand::GetComputedRole(ref x) => (Route::GetComputedRole, element_id(x)),
+        WebDriverCommand::GetComputedLabel(ref x) => (Route::GetComputedLabel, element_id(x)),
+        WebDriverCommand::GetElementText(ref x) => (Route::GetElementText, element_id(x)),
+        WebDriverCommand::GetElementTagName(ref x) => (Route::GetElementTagName, element_id(x)),
+        WebDriverCommand::GetElementRect(ref x) => (Route::GetElementRect, element_id(x)),
+        WebDriverCommand::IsEnabled(ref x) => (Route::IsEnabled, element_id(x)),
+        WebDriverCommand::ElementClick(ref x) => (Route::ElementClick, element_id(x)),
+        WebDriverCommand::ElementTap(ref x) => (Route::ElementTap, element_id(x)),
+        WebDriverCommand::ElementClear(ref x) => (Route::ElementClear, element_id(x)),
+        WebDriverCommand::ElementSendKeys(ref x, _) => (Route::ElementSendKeys, element_id(x)),
+        WebDriverCommand::TakeElementScreenshot(ref x) => (Route::TakeElementScreenshot, element_id(x)),
+        WebDriverCommand::GetElementAttribute(ref element, ref name) => {
+            (Route::GetElementAttribute,
+             vec![("elementId", element.id.clone()), ("name", name.clone())])
+        },
+        WebDriverCommand::GetElementProperty(ref element, ref name) => {
+            (Route::GetElementProperty,
+             vec![("elementId", element.id.clone()), ("name", name.clone())])
+        },
+        WebDriverCommand::GetCSSValue(ref element, ref name) => {
+            (Route::GetCSSValue,
+             vec![("elementId", element.id.clone()), ("propertyName", name.clone())])
+        },
+        WebDriverCommand::FindShadowRootElement(ref shadow_root, _) => {
+            (Route::FindShadowRootElement, vec![("shadowId", shadow_root.id.clone())])
+        },
+        WebDriverCommand::FindShadowRootElements(ref shadow_root, _) => {
+            (Route::FindShadowRootElements, vec![("shadowId", shadow_root.id.clone())])
+        },
+        WebDriverCommand::GetNamedCookie(ref name) => (Route::GetNamedCookie, vec![("name", name.clone())]),
+        WebDriverCommand::DeleteCookie(ref name) => (Route::DeleteCookie, vec![("name", name.clone())]),
+        WebDriverCommand::RemoveVirtualAuthenticator(ref id) => {
+            (Route::RemoveVirtualAuthenticator, vec![("authenticatorId", id.clone())])
+        },
+        WebDriverCommand::AddCredential(ref id, _) => {
+            (Route::AddCredential, vec![("authenticatorId", id.clone())])
+        },
+        WebDriverCommand::GetCredentials(ref id) => {
+            (Route::GetCredentials, vec![("authenticatorId", id.clone())])
+        },
+        WebDriverCommand::RemoveCredential(ref id, ref credential_id) => {
+            (Route::RemoveCredential,
+             vec![("authenticatorId", id.clone()), ("credentialId", credential_id.clone())])
+        },
+        WebDriverCommand::RemoveAllCredentials(ref id) => {
+            (Route::RemoveAllCredentials, vec![("authenticatorId", id.clone())])
+        },
+        WebDriverCommand::SetUserVerified(ref id, _) => {
+            (Route::SetUserVerified, vec![("authenticatorId", id.clone())])
+        },
+        WebDriverCommand::Extension(ref extension) => {
+            let (route, params) = try!(U::route(extension));
+            (Route::Extension(route), params)
+        },
+    })
+}
+
+/// The body of the HTTP request for a message, in the form accepted by
+/// `WebDriverMessage::from_http`.
+fn request_body<U: WebDriverExtensionRoute>(msg: &WebDriverMessage<U>) -> Json {
+    match msg.command {
+        WebDriverCommand::NewSession(ref x) => return x.to_json(),
+        WebDriverCommand::AddCookie(ref x) => {
+            let mut data = BTreeMap::new();
+            data.insert("cookie".to_string(), x.to_json());
+            return Json::Object(data);
+        },
+        WebDriverCommand::SwitchToFrame(ref x) => {
+            if let FrameId::Element(ref element) = x.id {
+                let mut data = BTreeMap::new();
+                data.insert("id".to_string(), element.to_json());
+                return Json::Object(data);
+            }
+        },
+        _ => {}
+    }
+    match msg.to_json() {
+        Json::Object(mut data) => data.remove("parameters")
+            .unwrap_or_else(|| Json::Object(BTreeMap::new())),
+        _ => Json::Object(BTreeMap::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use command::{LocatorParameters, NewWindowParameters, VoidWebDriverExtensionCommand,
+                  WebDriverCommand, WebDriverMessage};
+    use common::{LocatorStrategy, ShadowRoot, WebElement, WindowType};
+    use error::ErrorStatus;
+    use hyper::method::Method::{self, Get, Post, Delete};
+    use super::{decode_path_segment, VoidWebDriverExtensionRoute, WebDriverHttpApi};
+
+    fn decode(method: Method, path: &str, body: &str)
+              -> WebDriverCommand<VoidWebDriverExtensionCommand> {
+        let api = WebDriverHttpApi::<VoidWebDriverExtensionRoute>::new(&[]);
+        let msg = api.decode_request(method, path, body).ok().unwrap();
+        assert_eq!(msg.session_id, Some("s1".into()));
+        msg.command
+    }
+
+    fn round_trip(method: Method, path: &str, body: &str) {
+        let api = WebDriverHttpApi::<VoidWebDriverExtensionRoute>::new(&[]);
+        let msg = api.decode_request(method.clone(), path, body).ok().unwrap();
+        let (encoded_method, encoded_path, encoded_body) = api.encode_request(&msg).unwrap();
+        assert_eq!(encoded_method, method);
+        assert_eq!(encoded_path, path);
+        let decoded = api.decode_request(encoded_method,
+                                         &encoded_path,
+                                         &encoded_body.unwrap_or_default()).ok().unwrap();
+        assert!(decoded == msg, "{} {} did not round trip", method, path);
+    }
+
+    #[test]
+    fn test_encode_request_round_trip() {
+        round_trip(Post, "/session", r#"{"desiredCapabilities": {"browserName": "firefox"}}"#);
+        round_trip(Get, "/status", "");
+        round_trip(Delete, "/session/s1", "");
+        round_trip(Post, "/session/s1/url", r#"{"url": "https://example.org/"}"#);
+        round_trip(Post, "/session/s1/window/maximize", "{}");
+        round_trip(Post, "/session/s1/window/rect", r#"{"x": 10, "width": 600}"#);
+        round_trip(Post, "/session/s1/timeouts", r#"{"script": null, "implicit": 100}"#);
+        round_trip(Post, "/session/s1/frame", r#"{"id": 2}"#);
+        round_trip(Post, "/session/s1/frame",
+                   r#"{"id": {"element-6066-11e4-a52e-4f735466cecf": "e1"}}"#);
+        round_trip(Post, "/session/s1/element/e1/elements",
+                   r#"{"using": "css selector", "value": "a"}"#);
+        round_trip(Post, "/session/s1/shadow/sr1/element",
+                   r#"{"using": "css selector", "value": "a"}"#);
+        round_trip(Get, "/session/s1/element/e1/attribute/href", "");
+        round_trip(Get, "/session/s1/element/e1/css/color", "");
+        round_trip(Post, "/session/s1/element/e1/value", r#"{"value": ["a", "b"]}"#);
+        round_trip(Post, "/session/s1/element/e1/click", "{}");
+        round_trip(Post, "/session/s1/execute/sync", r#"{"script": "return 1", "args": []}"#);
+        round_trip(Post, "/session/s1/cookie", r#"{"cookie": {"name": "a", "value": "b"}}"#);
+        round_trip(Delete, "/session/s1/cookie/a", "");
+        round_trip(Post, "/session/s1/actions",
+                   r#"{"actions": [{"type": "key", "id": "k",
+                                    "actions": [{"type": "keyDown", "value": "a"}]},
+                                   {"type": "none", "actions": [{"type": "pause"}]}]}"#);
+        round_trip(Post, "/session/s1/print", r#"{"pageRanges": ["1-2"]}"#);
+        round_trip(Delete, "/session/s1/webauthn/authenticator/a1/credentials/c1", "");
+        round_trip(Delete, "/session/s1/webauthn/authenticator/a1/credentials", "");
+        round_trip(Post, "/session/s1/window/minimize", "{}");
+        round_trip(Post, "/session/s1/window/new", r#"{"type": "window"}"#);
+        round_trip(Get, "/session/s1/element/e1/shadow", "");
+        round_trip(Get, "/session/s1/element/e1/computedlabel", "");
+
+        // Path parameters are percent-encoded
+        round_trip(Get, "/session/s1/element/a%2Fb%3Fc%23d%25e%20f/text", "");
+        round_trip(Delete, "/session/s1/cookie/%C3%A9t%C3%A9", "");
+    }
+
+    #[test]
+    fn test_encode_path_segment() {
+        let api = WebDriverHttpApi::<VoidWebDriverExtensionRoute>::new(&[]);
+        let element = WebElement::new("a/b?c#d%e f".into());
+        let msg = WebDriverMessage::<VoidWebDriverExtensionRoute>::new(
+            Some("s1".into()), WebDriverCommand::GetElementText(element.clone()));
+        let (_, path, _) = api.encode_request(&msg).unwrap();
+        assert_eq!(path, "/session/s1/element/a%2Fb%3Fc%23d%25e%20f/text");
+        assert!(decode(Get, &path, "") == WebDriverCommand::GetElementText(element));
+
+        // A stray % is passed through
+        assert!(decode(Get, "/session/s1/cookie/100%", "") ==
+                WebDriverCommand::GetNamedCookie("100%".into()));
+        assert_eq!(decode_path_segment("%zz%4"), "%zz%4");
+    }
+
+    #[test]
+    fn test_encode_request_prefers_w3c_route() {
+        let api = WebDriverHttpApi::<VoidWebDriverExtensionRoute>::new(&[]);
+        let msg = api.decode_request(Get, "/session/s1/alert_text", "").ok().unwrap();
+        let (method, path, body) = api.encode_request(&msg).unwrap();
+        assert_eq!(method, Get);
+        assert_eq!(path, "/session/s1/alert/text");
+        assert_eq!(body, None);
+
+        let msg = WebDriverMessage::<VoidWebDriverExtensionRoute>::new(None,
+                                                                        WebDriverCommand::GetTitle);
+        assert!(api.encode_request(&msg).is_err());
+    }
+
+    #[test]
+    fn test_window_state_routes() {
+        assert!(decode(Post, "/session/s1/window/maximize", "{}") ==
+                WebDriverCommand::MaximizeWindow);
+        assert!(decode(Post, "/session/s1/window/minimize", "{}") ==
+                WebDriverCommand::MinimizeWindow);
+        assert!(decode(Post, "/session/s1/window/fullscreen", "{}") ==
+                WebDriverCommand::FullscreenWindow);
+
+        let api = WebDriverHttpApi::<VoidWebDriverExtensionRoute>::new(&[]);
+        assert!(api.decode_request(Get, "/session/s1/window/minimize", "").is_err());
+        match api.decode_request(Post, "/session/s1/window/fullscreen", "[]") {
+            Err(e) => assert_eq!(e.error, ErrorStatus::InvalidArgument),
+            Ok(_) => panic!("Fullscreen accepted a non-object body")
+        }
+    }
+
+    #[test]
+    fn test_new_window_route() {
+        assert!(decode(Post, "/session/s1/window/new", "{}") ==
+                WebDriverCommand::NewWindow(NewWindowParameters { type_hint: None }));
+        assert!(decode(Post, "/session/s1/window/new", r#"{"type": "tab"}"#) ==
+                WebDriverCommand::NewWindow(NewWindowParameters {
+                    type_hint: Some(WindowType::Tab)
+                }));
+    }
+
+    #[test]
+    fn test_shadow_root_routes() {
+        assert!(decode(Get, "/session/s1/element/e1/shadow", "") ==
+                WebDriverCommand::GetShadowRoot(WebElement::new("e1".into())));
+
+        let body = r#"{"using": "css selector", "value": "slot"}"#;
+        let locator = LocatorParameters {
+            using: LocatorStrategy::CSSSelector,
+            value: "slot".into(),
+        };
+        assert!(decode(Post, "/session/s1/shadow/sr1/element", body) ==
+                WebDriverCommand::FindShadowRootElement(ShadowRoot::new("sr1".into()),
+                                                        locator.clone()));
+        assert!(decode(Post, "/session/s1/shadow/sr1/elements", body) ==
+                WebDriverCommand::FindShadowRootElements(ShadowRoot::new("sr1".into()), locator));
+
+        let api = WebDriverHttpApi::<VoidWebDriverExtensionRoute>::new(&[]);
+        for path in &["/session/s1/shadow/sr1/element", "/session/s1/shadow/sr1/elements"] {
+            for body in &["{}", r#"{"value": "slot"}"#, r#"{"using": "css selector"}"#] {
+                match api.decode_request(Post, path, body) {
+                    Err(e) => assert_eq!(e.error, ErrorStatus::InvalidArgument),
+                    Ok(_) => panic!("{} accepted {}", path, body)
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_computed_accessibility_routes() {
+        assert!(decode(Get, "/session/s1/element/e1/computedrole", "") ==
+                WebDriverCommand::GetComputedRole(WebElement::new("e1".into())));
+        assert!(decode(Get, "/session/s1/element/e1/computedlabel", "") ==
+                WebDriverCommand::GetComputedLabel(WebElement::new("e1".into())));
+
+        let api = WebDriverHttpApi::<VoidWebDriverExtensionRoute>::new(&[]);
+        assert!(api.decode_request(Post, "/session/s1/element/e1/computedrole", "{}").is_err());
+        assert!(api.decode_request(Get, "/session/s1/element/computedlabel", "").is_err());
+    }
 }
@@ -0,0 +1,500 @@
+//! Planning the dispatch of a `PerformActions` command, and tracking the
+//! resulting input state.
+//!
+//! The action sequences are transposed into ticks, as in the "dispatch
+//! actions" algorithm of the specification. Each tick has a duration, and
+//! the actions of a tick should be run in order before waiting out the
+//! remainder of that duration.
+//!
+//! The server records the actions of a `PerformActions` command in the
+//! session's input state once the handler performed them, and gives the
+//! handler the actions that a `ReleaseActions` command must perform.
+
+use std::cmp;
+use std::collections::{BTreeMap, BTreeSet};
+
+use command::{ActionsParameters, ActionsType, GeneralAction, KeyAction, KeyActionItem,
+              KeyUpAction, NullActionItem, PointerAction, PointerActionItem, PointerType,
+              PointerUpAction, WheelAction, WheelActionItem};
+use common::Nullable;
+use error::{ErrorStatus, WebDriverError, WebDriverResult};
+
+/// An action to inject through an input source. Pauses aren't included.
+#[derive(PartialEq)]
+pub enum InputAction {
+    Key(KeyAction),
+    Pointer(PointerType, PointerAction),
+    Wheel(WheelAction),
+}
+
+#[derive(PartialEq)]
+pub struct SourceAction {
+    pub id: String,
+    pub action: InputAction,
+}
+
+#[derive(PartialEq)]
+pub struct Tick {
+    /// The duration of the tick in milliseconds.
+    pub duration: u64,
+    pub actions: Vec<SourceAction>,
+}
+
+/// An action of a sequence, which is `None` for a pause.
+type PlannedAction = (u64, Option<InputAction>);
+
+fn pause(action: GeneralAction) -> PlannedAction {
+    match action {
+        GeneralAction::Pause(x) => (x.duration, None),
+    }
+}
+
+fn sequence_actions(actions: ActionsType) -> Vec<PlannedAction> {
+    match actions {
+        ActionsType::Null(actions) => {
+            actions.into_iter().map(|x| match x {
+                NullActionItem::General(x) => pause(x),
+            }).collect()
+        },
+        ActionsType::Key(actions) => {
+            actions.into_iter().map(|x| match x {
+                KeyActionItem::General(x) => pause(x),
+                KeyActionItem::Key(x) => (0, Some(InputAction::Key(x))),
+            }).collect()
+        },
+        ActionsType::Pointer(parameters, actions) => {
+            let pointer_type = parameters.pointer_type;
+            actions.into_iter().map(|x| match x {
+                PointerActionItem::General(x) => pause(x),
+                PointerActionItem::Pointer(x) => {
+                    let duration = match x {
+                        PointerAction::Move(ref x) => x.duration.clone().into(),
+                        _ => None
+                    };
+                    (duration.unwrap_or(0), Some(InputAction::Pointer(pointer_type, x)))
+                },
+            }).collect()
+        },
+        ActionsType::Wheel(actions) => {
+            actions.into_iter().map(|x| match x {
+                WheelActionItem::General(x) => pause(x),
+                WheelActionItem::Wheel(WheelAction::Scroll(x)) => {
+                    let duration: Option<u64> = x.duration.clone().into();
+                    (duration.unwrap_or(0), Some(InputAction::Wheel(WheelAction::Scroll(x))))
+                },
+            }).collect()
+        },
+    }
+}
+
+/// Set the duration of a move or scroll without one to that of its tick.
+fn set_default_duration(action: &mut InputAction, duration: u64) {
+    let action_duration = match *action {
+        InputAction::Pointer(_, PointerAction::Move(ref mut x)) => &mut x.duration,
+        InputAction::Wheel(WheelAction::Scroll(ref mut x)) => &mut x.duration,
+        _ => return
+    };
+    if action_duration.is_null() {
+        *action_duration = Nullable::Value(duration);
+    }
+}
+
+/// Get the input source ids of the sequences of `parameters`, checking
+/// that each sequence has one and that they are unique.
+fn sequence_ids(parameters: &ActionsParameters) -> WebDriverResult<Vec<String>> {
+    let mut ids = BTreeSet::new();
+    let mut result = Vec::with_capacity(parameters.actions.len());
+    for sequence in parameters.actions.iter() {
+        let id = match sequence.id {
+            Nullable::Value(ref x) => x.clone(),
+            Nullable::Null => return Err(WebDriverError::new(
+                ErrorStatus::InvalidArgument,
+                "Action sequence is missing an input source id"))
+        };
+        if !ids.insert(id.clone()) {
+            return Err(WebDriverError::new(
+                ErrorStatus::InvalidArgument,
+                format!("Duplicate input source id {}", id)));
+        }
+        result.push(id);
+    }
+    Ok(result)
+}
+
+/// Transpose the action sequences of `parameters` into ticks.
+///
+/// The duration of a tick is the longest of its pauses and of the durations
+/// of its pointer moves and scrolls. Moves and scrolls without a duration
+/// are given that of their tick.
+pub fn plan_ticks(parameters: ActionsParameters) -> WebDriverResult<Vec<Tick>> {
+    let ids = try!(sequence_ids(&parameters));
+    let mut sequences = ids.into_iter().zip(parameters.actions.into_iter())
+        .map(|(id, sequence)| (id, sequence_actions(sequence.actions).into_iter()))
+        .collect::<Vec<_>>();
+
+    let tick_count = sequences.iter().map(|&(_, ref x)| x.len()).max().unwrap_or(0);
+    let mut ticks = Vec::with_capacity(tick_count);
+    for _ in 0..tick_count {
+        let mut duration = 0;
+        let mut actions = vec![];
+        for &mut (ref id, ref mut sequence) in sequences.iter_mut() {
+            if let Some((action_duration, action)) = sequence.next() {
+                duration = cmp::max(duration, action_duration);
+                if let Some(action) = action {
+                    actions.push(SourceAction {
+                        id: id.clone(),
+                        action: action,
+                    });
+                }
+            }
+        }
+        for action in actions.iter_mut() {
+            set_default_duration(&mut action.action, duration);
+        }
+        ticks.push(Tick {
+            duration: duration,
+            actions: actions,
+        });
+    }
+    Ok(ticks)
+}
+
+/// The state of a key input source.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct KeyInputState {
+    pub pressed: BTreeSet<char>,
+    pub alt: bool,
+    pub ctrl: bool,
+    pub meta: bool,
+    pub shift: bool,
+}
+
+impl KeyInputState {
+    /// Get the modifier flag set by `key`, if it is a modifier key.
+    fn modifier(&mut self, key: char) -> Option<&mut bool> {
+        match key {
+            '\u{E008}' | '\u{E050}' => Some(&mut self.shift),
+            '\u{E009}' | '\u{E051}' => Some(&mut self.ctrl),
+            '\u{E00A}' | '\u{E052}' => Some(&mut self.alt),
+            '\u{E03D}' | '\u{E053}' => Some(&mut self.meta),
+            _ => None
+        }
+    }
+}
+
+/// The state of a pointer input source.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PointerInputState {
+    pub pointer_type: PointerType,
+    pub pressed: BTreeSet<u64>,
+    /// The position of the pointer in the viewport. The library can't tell
+    /// where a move relative to an element ends, so it is `None` after one.
+    pub position: Option<(u64, u64)>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum InputSourceState {
+    Null,
+    Key(KeyInputState),
+    Pointer(PointerInputState),
+    Wheel,
+}
+
+impl InputSourceState {
+    fn new(actions: &ActionsType) -> InputSourceState {
+        match *actions {
+            ActionsType::Null(_) => InputSourceState::Null,
+            ActionsType::Key(_) => InputSourceState::Key(Default::default()),
+            ActionsType::Pointer(ref parameters, _) => {
+                InputSourceState::Pointer(PointerInputState {
+                    pointer_type: parameters.pointer_type,
+                    pressed: BTreeSet::new(),
+                    position: Some((0, 0)),
+                })
+            },
+            ActionsType::Wheel(_) => InputSourceState::Wheel,
+        }
+    }
+
+    /// Check that `actions` may be performed by this source.
+    fn check(&self, id: &str, actions: &ActionsType) -> WebDriverResult<()> {
+        let matches = match (self, actions) {
+            (&InputSourceState::Null, &ActionsType::Null(_)) |
+            (&InputSourceState::Key(_), &ActionsType::Key(_)) |
+            (&InputSourceState::Wheel, &ActionsType::Wheel(_)) => true,
+            (&InputSourceState::Pointer(ref state), &ActionsType::Pointer(ref parameters, _)) => {
+                state.pointer_type == parameters.pointer_type
+            },
+            _ => false
+        };
+        if matches {
+            Ok(())
+        } else {
+            Err(WebDriverError::new(
+                ErrorStatus::InvalidArgument,
+                format!("Input source {} was already used with a different type", id)))
+        }
+    }
+}
+
+/// An action that releases a pressed key or button.
+#[derive(Clone, Debug, PartialEq)]
+enum UndoAction {
+    KeyUp(char),
+    PointerUp(PointerType, u64),
+}
+
+/// The input state of a session, which is the state of the input sources
+/// used by its `PerformActions` commands.
+///
+/// A `ReleaseActions` command releases the keys and buttons that are still
+/// pressed, in the reverse of the order they were pressed in, and then
+/// resets the state.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct InputState {
+    sources: BTreeMap<String, InputSourceState>,
+    undo_actions: Vec<(String, UndoAction)>,
+}
+
+impl InputState {
+    pub fn new() -> InputState {
+        Default::default()
+    }
+
+    pub fn source(&self, id: &str) -> Option<&InputSourceState> {
+        self.sources.get(id)
+    }
+
+    /// Check that the sequences of `parameters` have unique input source
+    /// ids, and that the sources have the same types as earlier uses of
+    /// their ids.
+    pub fn check(&self, parameters: &ActionsParameters) -> WebDriverResult<()> {
+        let ids = try!(sequence_ids(parameters));
+        for (id, sequence) in ids.iter().zip(parameters.actions.iter()) {
+            if let Some(state) = self.sources.get(id) {
+                try!(state.check(id, &sequence.actions));
+            }
+        }
+        Ok(())
+    }
+
+    /// Check `parameters` and add the input sources they use to the state,
+    /// before any of their actions is performed.
+    pub fn add_sources(&mut self, parameters: &ActionsParameters) -> WebDriverResult<()> {
+        try!(self.check(parameters));
+        for sequence in parameters.actions.iter() {
+            if let Nullable::Value(ref id) = sequence.id {
+                if !self.sources.contains_key(id) {
+                    self.sources.insert(id.clone(), InputSourceState::new(&sequence.actions));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Update the state with actions that were performed.
+    pub fn update(&mut self, parameters: ActionsParameters) -> WebDriverResult<()> {
+        try!(self.add_sources(&parameters));
+        for tick in try!(plan_ticks(parameters)) {
+            for action in tick.actions {
+                self.apply(action);
+            }
+        }
+        Ok(())
+    }
+
+    fn apply(&mut self, action: SourceAction) {
+        let id = action.id;
+        // Pressing a key or button adds an undo action, releasing it removes it
+        let (pressed, undo) = match (self.sources.get_mut(&id), action.action) {
+            (Some(&mut InputSourceState::Key(ref mut state)), InputAction::Key(action)) => {
+                let (pressed, value) = match action {
+                    KeyAction::Down(x) => (true, x.value),
+                    KeyAction::Up(x) => (false, x.value),
+                };
+                if let Some(flag) = state.modifier(value) {
+                    *flag = pressed;
+                }
+                let changed = if pressed {
+                    state.pressed.insert(value)
+                } else {
+                    state.pressed.remove(&value)
+                };
+                if !changed {
+                    return;
+                }
+                (pressed, UndoAction::KeyUp(value))
+            },
+            (Some(&mut InputSourceState::Pointer(ref mut state)), InputAction::Pointer(_, action)) => {
+                let (pressed, button) = match action {
+                    PointerAction::Down(x) => (true, x.button),
+                    PointerAction::Up(x) => (false, x.button),
+                    PointerAction::Move(x) => {
+                        state.position = match (x.element, x.x, x.y) {
+                            (Nullable::Null, Nullable::Value(x), Nullable::Value(y)) => Some((x, y)),
+                            _ => None
+                        };
+                        return;
+                    },
+                    PointerAction::Cancel => return,
+                };
+                let changed = if pressed {
+                    state.pressed.insert(button)
+                } else {
+                    state.pressed.remove(&button)
+                };
+                if !changed {
+                    return;
+                }
+                (pressed, UndoAction::PointerUp(state.pointer_type, button))
+            },
+            _ => return
+        };
+
+        let entry = (id, undo);
+        if pressed {
+            self.undo_actions.push(entry);
+        } else {
+            self.undo_actions.retain(|x| *x != entry);
+        }
+    }
+
+    /// Get the actions a `ReleaseActions` command must perform.
+    pub fn release_actions(&self) -> Vec<SourceAction> {
+        self.undo_actions.iter().rev().map(|&(ref id, ref undo)| {
+            let action = match *undo {
+                UndoAction::KeyUp(value) => {
+                    InputAction::Key(KeyAction::Up(KeyUpAction { value: value }))
+                },
+                UndoAction::PointerUp(pointer_type, button) => {
+                    InputAction::Pointer(pointer_type,
+                                         PointerAction::Up(PointerUpAction { button: button }))
+                },
+            };
+            SourceAction {
+                id: id.clone(),
+                action: action,
+            }
+        }).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use command::{ActionsParameters, KeyAction, Parameters, PointerAction, PointerType,
+                  WheelAction};
+    use common::Nullable;
+    use error::ErrorStatus;
+    use rustc_serialize::json::Json;
+    use super::{plan_ticks, InputAction, InputSourceState, InputState};
+
+    fn parameters(body: &str) -> ActionsParameters {
+        Parameters::from_json(&Json::from_str(body).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_plan_ticks() {
+        let ticks = plan_ticks(parameters(r#"{"actions": [
+            {"type": "key", "id": "keyboard", "actions": [
+                {"type": "keyDown", "value": "a"},
+                {"type": "pause", "duration": 20},
+                {"type": "keyUp", "value": "a"}]},
+            {"type": "pointer", "id": "pen", "parameters": {"pointerType": "pen"}, "actions": [
+                {"type": "pointerMove", "x": 5, "y": 5},
+                {"type": "pointerMove", "x": 10, "y": 10, "duration": 50}]},
+            {"type": "wheel", "id": "wheel", "actions": [
+                {"type": "pause"},
+                {"type": "scroll", "x": 0, "y": 0, "deltaX": 0, "deltaY": 10}]},
+            {"type": "none", "id": "none", "actions": [
+                {"type": "pause", "duration": 100}]}]}"#)).unwrap();
+
+        assert_eq!(ticks.iter().map(|x| x.duration).collect::<Vec<_>>(), vec![100, 50, 0]);
+        let ids = |index: usize| -> Vec<&str> {
+            ticks[index].actions.iter().map(|x| &*x.id).collect()
+        };
+        assert_eq!(ids(0), vec!["keyboard", "pen"]);
+        assert_eq!(ids(1), vec!["pen", "wheel"]);
+        assert_eq!(ids(2), vec!["keyboard"]);
+
+        match ticks[0].actions[1].action {
+            InputAction::Pointer(PointerType::Pen, PointerAction::Move(ref x)) => {
+                assert_eq!(x.duration, Nullable::Value(100));
+            },
+            _ => panic!("Expected a pen move")
+        }
+        match ticks[1].actions[1].action {
+            InputAction::Wheel(WheelAction::Scroll(ref x)) => {
+                assert_eq!(x.duration, Nullable::Value(50));
+            },
+            _ => panic!("Expected a scroll")
+        }
+        match ticks[2].actions[0].action {
+            InputAction::Key(KeyAction::Up(ref x)) => assert_eq!(x.value, 'a'),
+            _ => panic!("Expected a key up")
+        }
+    }
+
+    #[test]
+    fn test_invalid_ids() {
+        let err = plan_ticks(parameters(r#"{"actions": [
+            {"type": "key", "id": "input", "actions": []},
+            {"type": "none", "id": "input", "actions": []}]}"#)).err().unwrap();
+        assert_eq!(err.error, ErrorStatus::InvalidArgument);
+
+        let err = plan_ticks(parameters(r#"{"actions": [
+            {"type": "key", "actions": []}]}"#)).err().unwrap();
+        assert_eq!(err.error, ErrorStatus::InvalidArgument);
+
+        let input_state = InputState::new();
+        for body in &[r#"{"actions": [{"type": "key", "actions": []}]}"#,
+                      r#"{"actions": [{"type": "key", "id": "a", "actions": []},
+                                      {"type": "key", "id": "a", "actions": []}]}"#] {
+            let err = input_state.check(&parameters(body)).err().unwrap();
+            assert_eq!(err.error, ErrorStatus::InvalidArgument);
+        }
+    }
+
+    #[test]
+    fn test_input_state() {
+        let mut input_state = InputState::new();
+        input_state.update(parameters(r#"{"actions": [
+            {"type": "key", "id": "keyboard", "actions": [
+                {"type": "keyDown", "value": "\uE008"},
+                {"type": "keyDown", "value": "a"},
+                {"type": "keyDown", "value": "b"},
+                {"type": "keyUp", "value": "a"}]},
+            {"type": "pointer", "id": "mouse", "actions": [
+                {"type": "pointerMove", "x": 5, "y": 10},
+                {"type": "pointerDown", "button": 2}]}]}"#)).unwrap();
+
+        match input_state.source("keyboard") {
+            Some(&InputSourceState::Key(ref x)) => {
+                assert_eq!(x.pressed.iter().cloned().collect::<String>(), "b\u{E008}");
+                assert!(x.shift && !x.ctrl);
+            },
+            _ => panic!("Expected a key input source")
+        }
+        match input_state.source("mouse") {
+            Some(&InputSourceState::Pointer(ref x)) => {
+                assert_eq!(x.pointer_type, PointerType::Mouse);
+                assert_eq!(x.position, Some((5, 10)));
+            },
+            _ => panic!("Expected a pointer input source")
+        }
+
+        let released = input_state.release_actions();
+        assert_eq!(released.iter().map(|x| &*x.id).collect::<Vec<_>>(),
+                   vec!["keyboard", "mouse", "keyboard"]);
+        match (&released[0].action, &released[2].action) {
+            (&InputAction::Key(KeyAction::Up(ref x)), &InputAction::Key(KeyAction::Up(ref y))) => {
+                assert_eq!((x.value, y.value), ('b', '\u{E008}'));
+            },
+            _ => panic!("Expected key ups")
+        }
+
+        let err = input_state.check(&parameters(r#"{"actions": [
+            {"type": "pointer", "id": "mouse", "parameters": {"pointerType": "touch"},
+             "actions": []}]}"#)).err().unwrap();
+        assert_eq!(err.error, ErrorStatus::InvalidArgument);
+    }
+}
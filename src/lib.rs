@@ -8,12 +8,27 @@ extern crate hyper;
 extern crate regex;
 extern crate cookie;
 extern crate time;
+#[cfg(feature = "async")]
+extern crate futures;
+#[cfg(feature = "async")]
+extern crate hyper_async;
+#[cfg(all(test, feature = "async"))]
+extern crate tokio;
 
 #[macro_use] pub mod macros;
+pub mod actions;
 pub mod httpapi;
+pub mod capabilities;
+pub mod client;
+#[cfg(feature = "async")]
+pub mod async_client;
+#[cfg(feature = "async")]
+pub mod async_server;
 pub mod command;
 pub mod common;
 pub mod error;
+pub mod fluent;
+pub mod input;
 pub mod server;
 pub mod response;
 
@@ -1,10 +1,14 @@
-use rustc_serialize::json;
+use rustc_serialize::{json, Encodable, Encoder};
+use rustc_serialize::json::{Json, ToJson};
+use std::collections::BTreeMap;
 
-use common::{Nullable, Date};
+use command::{WebDriverCommand, WebDriverExtensionCommand};
+use common::{Nullable, Date, WindowType};
+use error::{WebDriverResult, WebDriverError, ErrorStatus};
 use cookie;
 use time;
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub enum WebDriverResponse {
     CloseWindow(CloseWindowResponse),
     Cookie(CookieResponse),
@@ -12,28 +16,135 @@ pub enum WebDriverResponse {
     ElementRect(ElementRectResponse),
     Generic(ValueResponse),
     NewSession(NewSessionResponse),
+    NewWindow(NewWindowResponse),
+    /// The response to `GetComputedRole` and `GetComputedLabel`.
+    Text(TextResponse),
     Timeouts(TimeoutsResponse),
     Void,
     WindowPosition(WindowPositionResponse),
+    /// The response to `GetWindowRect`, `SetWindowRect`, `MaximizeWindow`,
+    /// `MinimizeWindow` and `FullscreenWindow`.
+    WindowRect(WindowRectResponse),
     WindowSize(WindowSizeResponse),
 }
 
 impl WebDriverResponse {
-    pub fn to_json_string(self) -> String {
-        let obj = match self {
+    /// Decode the body of a successful response to `command`.
+    ///
+    /// Commands that don't return data give a `Void` response and the
+    /// remaining commands without a specific response type a `Generic` one.
+    pub fn from_json<T>(command: &WebDriverCommand<T>, body: &Json) -> WebDriverResult<WebDriverResponse>
+        where T: WebDriverExtensionCommand
+    {
+        if !body.is_object() {
+            return Err(WebDriverError::new(ErrorStatus::UnknownError,
+                                           "Response body was not an object"));
+        }
+        let value = body.find("value");
+
+        let response = match *command {
+            WebDriverCommand::NewSession(_) => {
+                // Older remote ends send the session id next to the value
+                let data = match value {
+                    Some(x) if x.find("sessionId").is_some() => x,
+                    _ => body
+                };
+                WebDriverResponse::NewSession(try!(NewSessionResponse::from_json(data)))
+            },
+            WebDriverCommand::DeleteSession => WebDriverResponse::DeleteSession,
+            WebDriverCommand::CloseWindow => {
+                WebDriverResponse::CloseWindow(try!(CloseWindowResponse::from_json(
+                    try!(response_value(value)))))
+            },
+            WebDriverCommand::GetCookies |
+            WebDriverCommand::GetNamedCookie(_) => {
+                WebDriverResponse::Cookie(try!(CookieResponse::from_json(
+                    try!(response_value(value)))))
+            },
+            WebDriverCommand::GetElementRect(_) => {
+                WebDriverResponse::ElementRect(try!(ElementRectResponse::from_json(
+                    try!(response_value(value)))))
+            },
+            WebDriverCommand::NewWindow(_) => {
+                WebDriverResponse::NewWindow(try!(NewWindowResponse::from_json(
+                    try!(response_value(value)))))
+            },
+            WebDriverCommand::GetComputedRole(_) |
+            WebDriverCommand::GetComputedLabel(_) => {
+                WebDriverResponse::Text(try!(TextResponse::from_json(
+                    try!(response_value(value)))))
+            },
+            WebDriverCommand::GetTimeouts => {
+                WebDriverResponse::Timeouts(try!(TimeoutsResponse::from_json(
+                    try!(response_value(value)))))
+            },
+            WebDriverCommand::GetWindowPosition |
+            WebDriverCommand::SetWindowPosition(_) => {
+                WebDriverResponse::WindowPosition(try!(WindowPositionResponse::from_json(
+                    try!(response_value(value)))))
+            },
+            WebDriverCommand::GetWindowRect |
+            WebDriverCommand::SetWindowRect(_) |
+            WebDriverCommand::MaximizeWindow |
+            WebDriverCommand::MinimizeWindow |
+            WebDriverCommand::FullscreenWindow => {
+                WebDriverResponse::WindowRect(try!(WindowRectResponse::from_json(
+                    try!(response_value(value)))))
+            },
+            WebDriverCommand::GetWindowSize |
+            WebDriverCommand::SetWindowSize(_) => {
+                WebDriverResponse::WindowSize(try!(WindowSizeResponse::from_json(
+                    try!(response_value(value)))))
+            },
+            WebDriverCommand::Get(_) |
+            WebDriverCommand::GoBack |
+            WebDriverCommand::GoForward |
+            WebDriverCommand::Refresh |
+            WebDriverCommand::SwitchToWindow(_) |
+            WebDriverCommand::SwitchToFrame(_) |
+            WebDriverCommand::SwitchToParentFrame |
+            WebDriverCommand::AddCookie(_) |
+            WebDriverCommand::DeleteCookies |
+            WebDriverCommand::DeleteCookie(_) |
+            WebDriverCommand::SetTimeouts(_) |
+            WebDriverCommand::ElementClick(_) |
+            WebDriverCommand::ElementTap(_) |
+            WebDriverCommand::ElementClear(_) |
+            WebDriverCommand::ElementSendKeys(_, _) |
+            WebDriverCommand::PerformActions(_) |
+            WebDriverCommand::ReleaseActions |
+            WebDriverCommand::DismissAlert |
+            WebDriverCommand::AcceptAlert |
+            WebDriverCommand::SendAlertText(_) |
+            WebDriverCommand::SetPermission(_) |
+            WebDriverCommand::RemoveVirtualAuthenticator(_) |
+            WebDriverCommand::AddCredential(_, _) |
+            WebDriverCommand::RemoveCredential(_, _) |
+            WebDriverCommand::RemoveAllCredentials(_) |
+            WebDriverCommand::SetUserVerified(_, _) => WebDriverResponse::Void,
+            _ => WebDriverResponse::Generic(try!(ValueResponse::from_json(body)))
+        };
+        Ok(response)
+    }
+
+    pub fn to_json_string(&self) -> String {
+        let obj = match *self {
             WebDriverResponse::CloseWindow(ref x) => json::encode(x),
             WebDriverResponse::Cookie(ref x) => json::encode(x),
             WebDriverResponse::DeleteSession => Ok("{}".to_string()),
             WebDriverResponse::ElementRect(ref x) => json::encode(x),
             WebDriverResponse::Generic(ref x) => json::encode(x),
             WebDriverResponse::NewSession(ref x) => json::encode(x),
+            WebDriverResponse::NewWindow(ref x) => json::encode(x),
+            WebDriverResponse::Text(ref x) => json::encode(&x.value),
             WebDriverResponse::Timeouts(ref x) => json::encode(x),
             WebDriverResponse::Void => Ok("{}".to_string()),
             WebDriverResponse::WindowPosition(ref x) => json::encode(x),
+            WebDriverResponse::WindowRect(ref x) => json::encode(x),
             WebDriverResponse::WindowSize(ref x) => json::encode(x),
         }.unwrap();
 
-        match self {
+        match *self {
             WebDriverResponse::Generic(_) => obj,
             _ => {
                 let mut data = String::with_capacity(11 + obj.len());
@@ -46,24 +157,106 @@ impl WebDriverResponse {
     }
 }
 
-#[derive(RustcEncodable, Debug)]
+fn response_value(value: Option<&Json>) -> WebDriverResult<&Json> {
+    Ok(try_opt!(value,
+                ErrorStatus::UnknownError,
+                "Response body had no value"))
+}
+
+fn response_object(data: &Json) -> WebDriverResult<&BTreeMap<String, Json>> {
+    Ok(try_opt!(data.as_object(),
+                ErrorStatus::UnknownError,
+                "Response value was not an object"))
+}
+
+fn response_field<'a>(data: &'a BTreeMap<String, Json>, name: &str) -> WebDriverResult<&'a Json> {
+    Ok(try_opt!(data.get(name),
+                ErrorStatus::UnknownError,
+                format!("Response is missing '{}'", name)))
+}
+
+fn response_string(data: &BTreeMap<String, Json>, name: &str) -> WebDriverResult<String> {
+    Ok(try_opt!(try!(response_field(data, name)).as_string(),
+                ErrorStatus::UnknownError,
+                format!("Response field '{}' was not a string", name)).to_owned())
+}
+
+fn response_i64(data: &BTreeMap<String, Json>, name: &str) -> WebDriverResult<i64> {
+    Ok(try_opt!(try!(response_field(data, name)).as_i64(),
+                ErrorStatus::UnknownError,
+                format!("Response field '{}' was not an integer", name)))
+}
+
+fn response_u64(data: &BTreeMap<String, Json>, name: &str) -> WebDriverResult<u64> {
+    Ok(try_opt!(try!(response_field(data, name)).as_u64(),
+                ErrorStatus::UnknownError,
+                format!("Response field '{}' was not a positive integer", name)))
+}
+
+fn response_f64(data: &BTreeMap<String, Json>, name: &str) -> WebDriverResult<f64> {
+    Ok(try_opt!(try!(response_field(data, name)).as_f64(),
+                ErrorStatus::UnknownError,
+                format!("Response field '{}' was not a number", name)))
+}
+
+fn response_nullable_string(data: &BTreeMap<String, Json>, name: &str)
+                            -> WebDriverResult<Nullable<String>> {
+    match data.get(name) {
+        Some(x) => Nullable::from_json(x, |x| {
+            Ok(try_opt!(x.as_string(),
+                        ErrorStatus::UnknownError,
+                        format!("Response field '{}' was not a string", name)).to_owned())
+        }),
+        None => Ok(Nullable::Null)
+    }
+}
+
+#[derive(RustcEncodable, Debug, PartialEq)]
 pub struct CloseWindowResponse {
     pub window_handles: Vec<String>,
 }
 
 impl CloseWindowResponse {
+    /// Read the window handles, either as an object as sent by this crate
+    /// or as the plain array of the specification.
+    pub fn from_json(data: &Json) -> WebDriverResult<CloseWindowResponse> {
+        let handles = match data.find("window_handles") {
+            Some(x) => x,
+            None => data
+        };
+        let handles = try_opt!(handles.as_array(),
+                               ErrorStatus::UnknownError,
+                               "Window handles were not an array");
+        Ok(CloseWindowResponse::new(try!(handles.iter().map(|x| {
+            Ok(try_opt!(x.as_string(),
+                        ErrorStatus::UnknownError,
+                        "Window handle was not a string").to_owned())
+        }).collect::<WebDriverResult<Vec<_>>>())))
+    }
+
     pub fn new(handles: Vec<String>) -> CloseWindowResponse {
         CloseWindowResponse { window_handles: handles }
     }
 }
 
-#[derive(RustcEncodable, Debug)]
+#[derive(RustcEncodable, Debug, PartialEq)]
 pub struct NewSessionResponse {
     pub sessionId: String,
     pub value: json::Json
 }
 
 impl NewSessionResponse {
+    /// Read the session id and capabilities, which are in `value` or, for
+    /// W3C remote ends, in `capabilities`.
+    pub fn from_json(data: &Json) -> WebDriverResult<NewSessionResponse> {
+        let data = try!(response_object(data));
+        let capabilities = data.get("capabilities")
+            .or_else(|| data.get("value"))
+            .cloned()
+            .unwrap_or(Json::Null);
+        Ok(NewSessionResponse::new(try!(response_string(data, "sessionId")), capabilities))
+    }
+
     pub fn new(session_id: String, value: json::Json) -> NewSessionResponse {
         NewSessionResponse {
             value: value,
@@ -72,19 +265,95 @@ impl NewSessionResponse {
     }
 }
 
-#[derive(RustcEncodable, Debug)]
+#[derive(Debug, PartialEq)]
+pub struct NewWindowResponse {
+    pub handle: String,
+    pub window_type: WindowType,
+}
+
+impl NewWindowResponse {
+    pub fn from_json(data: &Json) -> WebDriverResult<NewWindowResponse> {
+        let data = try!(response_object(data));
+        let window_type = try!(WindowType::from_json(try!(response_field(data, "type"))));
+        Ok(NewWindowResponse::new(try!(response_string(data, "handle")), window_type))
+    }
+
+    pub fn new(handle: String, window_type: WindowType) -> NewWindowResponse {
+        NewWindowResponse {
+            handle: handle,
+            window_type: window_type,
+        }
+    }
+}
+
+impl Encodable for NewWindowResponse {
+    fn encode<S: Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
+        s.emit_struct("NewWindowResponse", 2, |s| {
+            try!(s.emit_struct_field("handle", 0, |s| self.handle.encode(s)));
+            s.emit_struct_field("type", 1, |s| self.window_type.to_json().encode(s))
+        })
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct TextResponse {
+    pub value: String
+}
+
+impl TextResponse {
+    pub fn from_json(data: &Json) -> WebDriverResult<TextResponse> {
+        Ok(TextResponse::new(try_opt!(data.as_string(),
+                                      ErrorStatus::UnknownError,
+                                      "Response value was not a string").to_owned()))
+    }
+
+    pub fn new(value: String) -> TextResponse {
+        TextResponse {
+            value: value
+        }
+    }
+}
+
+#[derive(RustcEncodable, Debug, PartialEq)]
 pub struct TimeoutsResponse {
-    pub script: u64,
+    pub script: Nullable<u64>,
     pub pageLoad: u64,
     pub implicit: u64,
 }
 
-#[derive(RustcEncodable, Debug)]
+impl TimeoutsResponse {
+    pub fn from_json(data: &Json) -> WebDriverResult<TimeoutsResponse> {
+        let data = try!(response_object(data));
+        let script = try!(Nullable::from_json(try!(response_field(data, "script")), |x| {
+            Ok(try_opt!(x.as_u64(),
+                        ErrorStatus::UnknownError,
+                        "Response field 'script' was not a positive integer"))
+        }));
+        Ok(TimeoutsResponse::new(script,
+                                 try!(response_u64(data, "pageLoad")),
+                                 try!(response_u64(data, "implicit"))))
+    }
+
+    pub fn new(script: Nullable<u64>, page_load: u64, implicit: u64) -> TimeoutsResponse {
+        TimeoutsResponse {
+            script: script,
+            pageLoad: page_load,
+            implicit: implicit,
+        }
+    }
+}
+
+#[derive(RustcEncodable, Debug, PartialEq)]
 pub struct ValueResponse {
     pub value: json::Json
 }
 
 impl ValueResponse {
+    pub fn from_json(data: &Json) -> WebDriverResult<ValueResponse> {
+        let data = try!(response_object(data));
+        Ok(ValueResponse::new(try!(response_field(data, "value")).clone()))
+    }
+
     pub fn new(value: json::Json) -> ValueResponse {
         ValueResponse {
             value: value
@@ -92,13 +361,19 @@ impl ValueResponse {
     }
 }
 
-#[derive(RustcEncodable, Debug)]
+#[derive(RustcEncodable, Debug, PartialEq)]
 pub struct WindowSizeResponse {
     pub width: u64,
     pub height: u64
 }
 
 impl WindowSizeResponse {
+    pub fn from_json(data: &Json) -> WebDriverResult<WindowSizeResponse> {
+        let data = try!(response_object(data));
+        Ok(WindowSizeResponse::new(try!(response_u64(data, "width")),
+                                   try!(response_u64(data, "height"))))
+    }
+
     pub fn new(width: u64, height: u64) -> WindowSizeResponse {
         WindowSizeResponse {
             width: width,
@@ -107,19 +382,52 @@ impl WindowSizeResponse {
     }
 }
 
-#[derive(RustcEncodable, Debug)]
+#[derive(RustcEncodable, Debug, PartialEq)]
 pub struct WindowPositionResponse {
     pub x: i64,
     pub y: i64,
 }
 
 impl WindowPositionResponse {
+    pub fn from_json(data: &Json) -> WebDriverResult<WindowPositionResponse> {
+        let data = try!(response_object(data));
+        Ok(WindowPositionResponse::new(try!(response_i64(data, "x")),
+                                       try!(response_i64(data, "y"))))
+    }
+
     pub fn new(x: i64, y: i64) -> WindowPositionResponse {
         WindowPositionResponse { x: x, y: y }
     }
 }
 
-#[derive(RustcEncodable, Debug)]
+#[derive(RustcEncodable, Debug, PartialEq)]
+pub struct WindowRectResponse {
+    pub x: i64,
+    pub y: i64,
+    pub width: u64,
+    pub height: u64,
+}
+
+impl WindowRectResponse {
+    pub fn from_json(data: &Json) -> WebDriverResult<WindowRectResponse> {
+        let data = try!(response_object(data));
+        Ok(WindowRectResponse::new(try!(response_i64(data, "x")),
+                                   try!(response_i64(data, "y")),
+                                   try!(response_u64(data, "width")),
+                                   try!(response_u64(data, "height"))))
+    }
+
+    pub fn new(x: i64, y: i64, width: u64, height: u64) -> WindowRectResponse {
+        WindowRectResponse {
+            x: x,
+            y: y,
+            width: width,
+            height: height
+        }
+    }
+}
+
+#[derive(RustcEncodable, Debug, PartialEq)]
 pub struct ElementRectResponse {
     pub x: f64,
     pub y: f64,
@@ -128,6 +436,14 @@ pub struct ElementRectResponse {
 }
 
 impl ElementRectResponse {
+    pub fn from_json(data: &Json) -> WebDriverResult<ElementRectResponse> {
+        let data = try!(response_object(data));
+        Ok(ElementRectResponse::new(try!(response_f64(data, "x")),
+                                    try!(response_f64(data, "y")),
+                                    try!(response_f64(data, "width")),
+                                    try!(response_f64(data, "height"))))
+    }
+
     pub fn new(x: f64, y: f64, width: f64, height: f64) -> ElementRectResponse {
         ElementRectResponse {
             x: x,
@@ -151,6 +467,31 @@ pub struct Cookie {
 }
 
 impl Cookie {
+    pub fn from_json(data: &Json) -> WebDriverResult<Cookie> {
+        let data = try!(response_object(data));
+        let expiry = match data.get("expiry") {
+            Some(x) => try!(Nullable::from_json(x, |x| {
+                Ok(Date::new(try_opt!(x.as_u64(),
+                                      ErrorStatus::UnknownError,
+                                      "Cookie expiry was not a positive integer")))
+            })),
+            None => Nullable::Null
+        };
+        let flag = |name| match data.get(name) {
+            Some(x) => Ok(try_opt!(x.as_boolean(),
+                                   ErrorStatus::UnknownError,
+                                   format!("Cookie field '{}' was not a boolean", name))),
+            None => Ok(false)
+        };
+        Ok(Cookie::new(try!(response_string(data, "name")),
+                       try!(response_string(data, "value")),
+                       try!(response_nullable_string(data, "path")),
+                       try!(response_nullable_string(data, "domain")),
+                       expiry,
+                       try!(flag("secure")),
+                       try!(flag("httpOnly"))))
+    }
+
     pub fn new(name: String, value: String, path: Nullable<String>, domain: Nullable<String>,
                expiry: Nullable<Date>, secure: bool, http_only: bool) -> Cookie {
         Cookie {
@@ -188,15 +529,195 @@ impl Into<cookie::Cookie<'static>> for Cookie {
     }
 }
 
-#[derive(RustcEncodable, Debug)]
+#[derive(RustcEncodable, Debug, PartialEq)]
 pub struct CookieResponse {
     pub value: Vec<Cookie>
 }
 
 impl CookieResponse {
+    /// Read the cookies, either as an object with the list in `value` as
+    /// sent by this crate, as a plain array, or as the single cookie
+    /// returned for a named cookie.
+    pub fn from_json(data: &Json) -> WebDriverResult<CookieResponse> {
+        let data = match data.find("value") {
+            Some(x @ &Json::Array(_)) => x,
+            _ => data
+        };
+        let cookies = match *data {
+            Json::Array(ref cookies) => {
+                try!(cookies.iter().map(Cookie::from_json).collect::<WebDriverResult<Vec<_>>>())
+            },
+            _ => vec![try!(Cookie::from_json(data))]
+        };
+        Ok(CookieResponse::new(cookies))
+    }
+
     pub fn new(value: Vec<Cookie>) -> CookieResponse {
         CookieResponse {
             value: value
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use command::{Parameters, WebDriverCommand, VoidWebDriverExtensionCommand};
+    use common::{Date, Nullable, ShadowRoot, WebElement, WindowType};
+    use rustc_serialize::json::{Json, ToJson};
+    use super::{CloseWindowResponse, Cookie, CookieResponse, ElementRectResponse,
+                NewSessionResponse, NewWindowResponse, TextResponse, TimeoutsResponse,
+                ValueResponse, WebDriverResponse, WindowRectResponse, WindowSizeResponse};
+
+    type Command = WebDriverCommand<VoidWebDriverExtensionCommand>;
+
+    fn empty_parameters<T: Parameters>() -> T {
+        Parameters::from_json(&Json::from_str("{}").unwrap()).unwrap()
+    }
+
+    fn round_trip(command: Command, response: WebDriverResponse) {
+        let body = Json::from_str(&response.to_json_string()).unwrap();
+        let decoded = WebDriverResponse::from_json(&command, &body).unwrap();
+        assert_eq!(decoded, response);
+    }
+
+    #[test]
+    fn test_response_round_trip() {
+        let capabilities = Json::from_str(r#"{"browserName": "firefox"}"#).unwrap();
+        round_trip(WebDriverCommand::NewSession(empty_parameters()),
+                   WebDriverResponse::NewSession(NewSessionResponse::new("s1".into(),
+                                                                         capabilities)));
+        round_trip(WebDriverCommand::DeleteSession, WebDriverResponse::DeleteSession);
+        round_trip(WebDriverCommand::CloseWindow,
+                   WebDriverResponse::CloseWindow(CloseWindowResponse::new(vec!["w2".into()])));
+        round_trip(WebDriverCommand::GetCookies,
+                   WebDriverResponse::Cookie(CookieResponse::new(vec![
+                       Cookie::new("a".into(), "b".into(), Nullable::Value("/".into()),
+                                   Nullable::Null, Nullable::Value(Date::new(10)), true, false)])));
+        round_trip(WebDriverCommand::GetTimeouts,
+                   WebDriverResponse::Timeouts(TimeoutsResponse::new(Nullable::Null, 300000, 0)));
+        round_trip(WebDriverCommand::MaximizeWindow,
+                   WebDriverResponse::WindowRect(WindowRectResponse::new(-5, 0, 800, 600)));
+        round_trip(WebDriverCommand::GetWindowSize,
+                   WebDriverResponse::WindowSize(WindowSizeResponse::new(800, 600)));
+        round_trip(WebDriverCommand::NewWindow(empty_parameters()),
+                   WebDriverResponse::NewWindow(NewWindowResponse::new("w3".into(),
+                                                                       WindowType::Tab)));
+        round_trip(WebDriverCommand::GetTitle,
+                   WebDriverResponse::Generic(ValueResponse::new("title".to_json())));
+        round_trip(WebDriverCommand::GetTitle,
+                   WebDriverResponse::Generic(ValueResponse::new(Json::Null)));
+        round_trip(WebDriverCommand::Refresh, WebDriverResponse::Void);
+    }
+
+    #[test]
+    fn test_w3c_responses() {
+        let command: Command = WebDriverCommand::GetElementRect(WebElement::new("e1".into()));
+        let body = Json::from_str(
+            r#"{"value": {"x": 1.5, "y": 2, "width": 10, "height": 20.25}}"#).unwrap();
+        assert_eq!(WebDriverResponse::from_json(&command, &body).unwrap(),
+                   WebDriverResponse::ElementRect(ElementRectResponse::new(1.5, 2.0, 10.0, 20.25)));
+
+        let body = Json::from_str(r#"{"value": {"name": "a", "value": "b"}}"#).unwrap();
+        let command: Command = WebDriverCommand::GetNamedCookie("a".into());
+        assert_eq!(WebDriverResponse::from_json(&command, &body).unwrap(),
+                   WebDriverResponse::Cookie(CookieResponse::new(vec![
+                       Cookie::new("a".into(), "b".into(), Nullable::Null, Nullable::Null,
+                                   Nullable::Null, false, false)])));
+
+        let body = Json::from_str(r#"{"value": ["w1", "w2"]}"#).unwrap();
+        let command: Command = WebDriverCommand::CloseWindow;
+        assert_eq!(WebDriverResponse::from_json(&command, &body).unwrap(),
+                   WebDriverResponse::CloseWindow(CloseWindowResponse::new(
+                       vec!["w1".into(), "w2".into()])));
+
+        let body = Json::from_str(
+            r#"{"value": {"sessionId": "s1", "capabilities": {"browserName": "chrome"}}}"#);
+        let command: Command = WebDriverCommand::NewSession(empty_parameters());
+        match WebDriverResponse::from_json(&command, &body.unwrap()).unwrap() {
+            WebDriverResponse::NewSession(x) => {
+                assert_eq!(x.sessionId, "s1");
+                assert_eq!(x.value.find("browserName"), Some(&"chrome".to_json()));
+            },
+            x => panic!("Unexpected response {:?}", x)
+        }
+
+        let body = Json::from_str(r#"{"value": {"width": -1, "height": 10}}"#).unwrap();
+        let command: Command = WebDriverCommand::GetWindowSize;
+        assert!(WebDriverResponse::from_json(&command, &body).is_err());
+    }
+
+    #[test]
+    fn test_window_state_responses() {
+        let body = Json::from_str(
+            r#"{"value": {"x": -8, "y": 0, "width": 1920, "height": 1080}}"#).unwrap();
+        for command in vec![WebDriverCommand::MaximizeWindow,
+                            WebDriverCommand::MinimizeWindow,
+                            WebDriverCommand::FullscreenWindow] as Vec<Command> {
+            assert_eq!(WebDriverResponse::from_json(&command, &body).unwrap(),
+                       WebDriverResponse::WindowRect(WindowRectResponse::new(-8, 0, 1920, 1080)));
+        }
+
+        let body = Json::from_str(r#"{"value": null}"#).unwrap();
+        let command: Command = WebDriverCommand::MinimizeWindow;
+        assert!(WebDriverResponse::from_json(&command, &body).is_err());
+    }
+
+    #[test]
+    fn test_new_window_response() {
+        let body = Json::from_str(r#"{"value": {"handle": "w2", "type": "window"}}"#).unwrap();
+        let command: Command = WebDriverCommand::NewWindow(empty_parameters());
+        assert_eq!(WebDriverResponse::from_json(&command, &body).unwrap(),
+                   WebDriverResponse::NewWindow(NewWindowResponse::new("w2".into(),
+                                                                       WindowType::Window)));
+
+        for body in &[r#"{"value": {"handle": "w2"}}"#,
+                      r#"{"value": {"handle": "w2", "type": "popup"}}"#,
+                      r#"{"value": {"type": "tab"}}"#] {
+            let body = Json::from_str(body).unwrap();
+            assert!(WebDriverResponse::from_json(&command, &body).is_err());
+        }
+    }
+
+    #[test]
+    fn test_shadow_root_response() {
+        let shadow_root = Json::from_str(
+            r#"{"shadow-6066-11e4-a52e-4f735466cecf": "sr1"}"#).unwrap();
+        assert_eq!(ShadowRoot::new("sr1".into()).to_json(), shadow_root);
+
+        let body = Json::from_str(
+            r#"{"value": {"shadow-6066-11e4-a52e-4f735466cecf": "sr1"}}"#).unwrap();
+        let command: Command = WebDriverCommand::GetShadowRoot(WebElement::new("e1".into()));
+        match WebDriverResponse::from_json(&command, &body).unwrap() {
+            WebDriverResponse::Generic(x) => {
+                assert_eq!(ShadowRoot::from_json(&x.value).unwrap(), ShadowRoot::new("sr1".into()));
+                assert!(WebElement::from_json(&x.value).is_err());
+            },
+            x => panic!("Unexpected response {:?}", x)
+        }
+
+        let element = WebElement::new("sr1".into()).to_json();
+        assert!(ShadowRoot::from_json(&element).is_err());
+    }
+
+    #[test]
+    fn test_computed_accessibility_responses() {
+        let element = WebElement::new("e1".into());
+        let command: Command = WebDriverCommand::GetComputedRole(element.clone());
+        let body = Json::from_str(r#"{"value": "button"}"#).unwrap();
+        assert_eq!(WebDriverResponse::from_json(&command, &body).unwrap(),
+                   WebDriverResponse::Text(TextResponse::new("button".into())));
+
+        // An element without an accessible name has an empty label
+        let command: Command = WebDriverCommand::GetComputedLabel(element);
+        let body = Json::from_str(r#"{"value": ""}"#).unwrap();
+        assert_eq!(WebDriverResponse::from_json(&command, &body).unwrap(),
+                   WebDriverResponse::Text(TextResponse::new("".into())));
+        assert_eq!(WebDriverResponse::Text(TextResponse::new("Submit".into())).to_json_string(),
+                   r#"{"value": "Submit"}"#);
+
+        for body in &[r#"{"value": null}"#, r#"{"value": ["button"]}"#, "{}"] {
+            let body = Json::from_str(body).unwrap();
+            assert!(WebDriverResponse::from_json(&command, &body).is_err());
+        }
+    }
+}
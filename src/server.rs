@@ -1,9 +1,12 @@
+use std::cmp;
+use std::collections::BTreeMap;
 use std::io::Read;
 use std::marker::PhantomData;
 use std::net::SocketAddr;
 use std::sync::mpsc::{channel, Receiver, Sender};
 use std::sync::Mutex;
 use std::thread;
+use std::time::Duration;
 
 use hyper::header::{ContentType, CacheControl, CacheDirective};
 use hyper::mime::{Mime, TopLevel, SubLevel, Attr, Value};
@@ -13,46 +16,172 @@ use hyper::server::{Handler, Listening, Request, Response, Server};
 use hyper::status::StatusCode;
 use hyper::uri::RequestUri::AbsolutePath;
 
-use command::{WebDriverMessage, WebDriverCommand};
-use error::{WebDriverResult, WebDriverError, ErrorStatus};
+use capabilities::{CapabilitiesRegistry, UnhandledPromptBehavior};
+use command::{NewSessionParameters, Parameters, TimeoutsParameters,
+              WebDriverMessage, WebDriverCommand, WebDriverExtensionCommand};
+use common::{Clock, SystemClock};
+use error::{set_backtrace_capture, BacktraceCapture, ErrorStatus, StacktraceReporting,
+            WebDriverResult, WebDriverError};
 use httpapi::{WebDriverHttpApi, WebDriverExtensionRoute, VoidWebDriverExtensionRoute};
-use response::{CloseWindowResponse, WebDriverResponse};
+use input::{InputState, SourceAction};
+use response::{CloseWindowResponse, TimeoutsResponse, WebDriverResponse};
+use rustc_serialize::json::{Json, ToJson};
 
 enum DispatchMessage<U: WebDriverExtensionRoute> {
     HandleWebDriver(WebDriverMessage<U>, Sender<WebDriverResult<WebDriverResponse>>),
-    Quit
 }
 
 #[derive(PartialEq, Clone)]
 pub struct Session {
-    id: String
+    pub(crate) id: String,
+    pub timeouts: Timeouts,
+    pub unhandled_prompt_behavior: UnhandledPromptBehavior,
+    /// The state of the input sources, updated by `PerformActions` and reset
+    /// by `ReleaseActions`.
+    pub input_state: InputState,
 }
 
 impl Session {
-    fn new(id: String) -> Session {
+    pub(crate) fn new(id: String,
+                      timeouts: Timeouts,
+                      unhandled_prompt_behavior: UnhandledPromptBehavior) -> Session {
         Session {
-            id: id
+            id: id,
+            timeouts: timeouts,
+            unhandled_prompt_behavior: unhandled_prompt_behavior,
+            input_state: InputState::new(),
         }
     }
 }
 
+/// The session timeouts, in milliseconds.
+#[derive(PartialEq, Clone, Debug)]
+pub struct Timeouts {
+    /// `None` if scripts never time out.
+    pub script: Option<u64>,
+    pub page_load: u64,
+    pub implicit: u64,
+}
+
+impl Timeouts {
+    /// Get the initial timeouts from the `timeouts` capability.
+    pub fn from_capabilities(parameters: &NewSessionParameters) -> WebDriverResult<Timeouts> {
+        let mut timeouts = Timeouts::default();
+        if let Some(capability) = parameters.get("timeouts") {
+            if !capability.is_object() {
+                return Err(WebDriverError::new(ErrorStatus::InvalidArgument,
+                                               "'timeouts' capability is not an object"));
+            }
+            timeouts.update(&try!(TimeoutsParameters::from_json(capability)));
+        }
+        Ok(timeouts)
+    }
+
+    /// Apply the timeouts present in `parameters`, leaving the others unchanged.
+    pub fn update(&mut self, parameters: &TimeoutsParameters) {
+        if let Some(ref script) = parameters.script {
+            self.script = script.clone().into();
+        }
+        if let Some(page_load) = parameters.page_load {
+            self.page_load = page_load;
+        }
+        if let Some(implicit) = parameters.implicit {
+            self.implicit = implicit;
+        }
+    }
+
+    pub fn to_response(&self) -> TimeoutsResponse {
+        TimeoutsResponse::new(self.script.into(), self.page_load, self.implicit)
+    }
+}
+
+impl Default for Timeouts {
+    fn default() -> Timeouts {
+        Timeouts {
+            script: Some(30000),
+            page_load: 300000,
+            implicit: 0,
+        }
+    }
+}
+
+/// Retry element location commands that find nothing until the session's
+/// implicit wait timeout expires.
+pub struct ImplicitWait {
+    pub poll_interval: Duration,
+    pub clock: Box<Clock>,
+}
+
+impl ImplicitWait {
+    pub fn new(poll_interval: Duration) -> ImplicitWait {
+        ImplicitWait {
+            poll_interval: poll_interval,
+            clock: Box::new(SystemClock),
+        }
+    }
+}
+
+/// Settings for the library side of command dispatch.
+#[derive(Default)]
+pub struct ServerConfig {
+    /// Parsers for vendor-prefixed capabilities, applied to the
+    /// capabilities of each `NewSession` command.
+    pub capabilities: CapabilitiesRegistry,
+    /// If set, the dispatcher implements the implicit wait for the element
+    /// location commands, calling the handler once per poll.
+    ///
+    /// Only `start_with_config` supports this; the async `serve` rejects a
+    /// config that sets it with an `unsupported operation` error.
+    pub implicit_wait: Option<ImplicitWait>,
+    /// How errors created on the server's threads, including by the
+    /// handler, capture a backtrace, and what is kept of the backtrace of
+    /// errors created elsewhere.
+    pub backtrace_capture: BacktraceCapture,
+    /// What errors returned by this server report in their `stacktrace`
+    /// field.
+    pub stacktrace_reporting: StacktraceReporting,
+}
+
 pub trait WebDriverHandler<U: WebDriverExtensionRoute=VoidWebDriverExtensionRoute> : Send {
     fn handle_command(&mut self, session: &Option<Session>, msg: WebDriverMessage<U>) -> WebDriverResult<WebDriverResponse>;
     fn delete_session(&mut self, session: &Option<Session>);
+
+    /// Run a `ReleaseActions` command.
+    ///
+    /// `actions` release the keys and buttons that are still pressed, and
+    /// must be performed in order. The session's input state is reset once
+    /// this succeeds. By default the command is passed to `handle_command`
+    /// and `actions` are ignored.
+    fn release_actions(&mut self, session: &Option<Session>, msg: WebDriverMessage<U>,
+                       _: Vec<SourceAction>) -> WebDriverResult<WebDriverResponse> {
+        self.handle_command(session, msg)
+    }
+
+    /// Report whether a user prompt is currently open.
+    ///
+    /// If it is, the dispatcher applies the session's
+    /// `unhandledPromptBehavior` before running commands that handle user
+    /// prompts, using the `GetAlertText`, `AcceptAlert` and `DismissAlert`
+    /// commands.
+    fn is_user_prompt_open(&mut self, _: &Option<Session>) -> WebDriverResult<bool> {
+        Ok(false)
+    }
 }
 
 struct Dispatcher<T: WebDriverHandler<U>,
                   U: WebDriverExtensionRoute> {
     handler: T,
     session: Option<Session>,
+    config: ServerConfig,
     extension_type: PhantomData<U>,
 }
 
 impl<T: WebDriverHandler<U>, U: WebDriverExtensionRoute> Dispatcher<T, U> {
-    fn new(handler: T) -> Dispatcher<T, U> {
+    fn new(handler: T, config: ServerConfig) -> Dispatcher<T, U> {
         Dispatcher {
             handler: handler,
             session: None,
+            config: config,
             extension_type: PhantomData,
         }
     }
@@ -61,110 +190,405 @@ impl<T: WebDriverHandler<U>, U: WebDriverExtensionRoute> Dispatcher<T, U> {
         loop {
             match msg_chan.recv() {
                 Ok(DispatchMessage::HandleWebDriver(msg, resp_chan)) => {
-                    let resp = match self.check_session(&msg) {
-                        Ok(_) => self.handler.handle_command(&self.session, msg),
-                        Err(e) => Err(e),
-                    };
-
-                    match resp {
-                        Ok(WebDriverResponse::NewSession(ref new_session)) => {
-                            self.session = Some(Session::new(new_session.sessionId.clone()));
-                        }
-                        Ok(WebDriverResponse::CloseWindow(CloseWindowResponse { ref window_handles })) => {
-                            if window_handles.len() == 0 {
-                                debug!("Last window was closed, deleting session");
-                                self.delete_session();
-                            }
-                        }
-                        Ok(WebDriverResponse::DeleteSession) => self.delete_session(),
-                        Err(ref x) if x.delete_session => self.delete_session(),
-                        _ => {}
-                    }
-
+                    let resp = self.dispatch(msg);
                     if resp_chan.send(resp).is_err() {
                         error!("Sending response to the main thread failed");
                     };
                 }
-                Ok(DispatchMessage::Quit) => break,
                 Err(_) => panic!("Error receiving message in handler"),
             }
         }
     }
 
+    fn dispatch(&mut self, msg: WebDriverMessage<U>) -> WebDriverResult<WebDriverResponse> {
+        let resp = match check_session(&self.session, &msg) {
+            Ok(_) => {
+                match self.handle_user_prompts(&msg) {
+                    Ok(_) => self.handle(msg),
+                    Err(e) => Err(e),
+                }
+            },
+            Err(e) => Err(e),
+        };
+
+        match resp {
+            Ok(WebDriverResponse::CloseWindow(CloseWindowResponse { ref window_handles })) => {
+                if window_handles.len() == 0 {
+                    debug!("Last window was closed, deleting session");
+                    self.delete_session();
+                }
+            }
+            Ok(WebDriverResponse::DeleteSession) => self.delete_session(),
+            Err(ref x) if x.delete_session => self.delete_session(),
+            _ => {}
+        }
+
+        resp
+    }
+
+    fn handle(&mut self, mut msg: WebDriverMessage<U>) -> WebDriverResult<WebDriverResponse> {
+        let implicit_wait = self.config.implicit_wait.is_some();
+        match msg.command {
+            WebDriverCommand::NewSession(ref mut parameters) => {
+                let (timeouts, prompt_behavior) =
+                    try!(new_session_settings(&self.config.capabilities, parameters));
+                self.new_session(msg, timeouts, prompt_behavior)
+            },
+            WebDriverCommand::GetTimeouts => self.get_timeouts(),
+            WebDriverCommand::SetTimeouts(_) => self.set_timeouts(msg),
+            WebDriverCommand::PerformActions(_) => self.perform_actions(msg),
+            WebDriverCommand::ReleaseActions => self.release_actions(msg),
+            WebDriverCommand::FindElement(ref x) if implicit_wait => {
+                self.find_with_implicit_wait(&msg.session_id,
+                                             || WebDriverCommand::FindElement(x.clone()))
+            },
+            WebDriverCommand::FindElements(ref x) if implicit_wait => {
+                self.find_with_implicit_wait(&msg.session_id,
+                                             || WebDriverCommand::FindElements(x.clone()))
+            },
+            WebDriverCommand::FindElementElement(ref element, ref x) if implicit_wait => {
+                self.find_with_implicit_wait(&msg.session_id, || {
+                    WebDriverCommand::FindElementElement(element.clone(), x.clone())
+                })
+            },
+            WebDriverCommand::FindElementElements(ref element, ref x) if implicit_wait => {
+                self.find_with_implicit_wait(&msg.session_id, || {
+                    WebDriverCommand::FindElementElements(element.clone(), x.clone())
+                })
+            },
+            WebDriverCommand::FindShadowRootElement(ref shadow_root, ref x) if implicit_wait => {
+                self.find_with_implicit_wait(&msg.session_id, || {
+                    WebDriverCommand::FindShadowRootElement(shadow_root.clone(), x.clone())
+                })
+            },
+            WebDriverCommand::FindShadowRootElements(ref shadow_root, ref x) if implicit_wait => {
+                self.find_with_implicit_wait(&msg.session_id, || {
+                    WebDriverCommand::FindShadowRootElements(shadow_root.clone(), x.clone())
+                })
+            },
+            _ => self.handler.handle_command(&self.session, msg),
+        }
+    }
+
+    fn handle_user_prompts(&mut self, msg: &WebDriverMessage<U>) -> WebDriverResult<()> {
+        let (session_id, behavior) = match self.session {
+            Some(ref session) if handles_user_prompts(&msg.command) => {
+                (session.id.clone(), session.unhandled_prompt_behavior.clone())
+            },
+            _ => return Ok(())
+        };
+        if !try!(self.handler.is_user_prompt_open(&self.session)) {
+            return Ok(());
+        }
+
+        let text = if needs_prompt_text(&behavior) {
+            let msg = WebDriverMessage::new(Some(session_id.clone()),
+                                            WebDriverCommand::GetAlertText);
+            match try!(prompt_text(self.handler.handle_command(&self.session, msg))) {
+                Some(text) => text,
+                // The prompt was closed in the meantime
+                None => return Ok(()),
+            }
+        } else {
+            String::new()
+        };
+
+        if let Some(command) = prompt_command(&behavior) {
+            let msg = WebDriverMessage::new(Some(session_id), command);
+            try!(prompt_closed(self.handler.handle_command(&self.session, msg)));
+        }
+
+        prompt_result(&behavior, text)
+    }
+
+    /// Run the element location command made by `command` until it finds
+    /// an element or the implicit wait timeout elapses.
+    fn find_with_implicit_wait<F>(&mut self, session_id: &Option<String>, command: F)
+                                  -> WebDriverResult<WebDriverResponse>
+        where F: Fn() -> WebDriverCommand<U::Command>
+    {
+        let wait = self.config.implicit_wait.as_ref().expect("Implicit wait is not enabled");
+        let timeout = self.session.as_ref().map(|x| x.timeouts.implicit).unwrap_or(0);
+        let deadline = wait.clock.now() + Duration::from_millis(timeout);
+
+        loop {
+            let attempt = WebDriverMessage::new(session_id.clone(), command());
+            let resp = self.handler.handle_command(&self.session, attempt);
+            let found = match resp {
+                Err(ref err) => err.error != ErrorStatus::NoSuchElement,
+                Ok(WebDriverResponse::Generic(ref x)) => {
+                    x.value.as_array().map_or(true, |elements| elements.len() > 0)
+                },
+                Ok(_) => true,
+            };
+
+            let now = wait.clock.now();
+            if found || now >= deadline {
+                return resp;
+            }
+            wait.clock.sleep(cmp::min(wait.poll_interval, deadline - now));
+        }
+    }
+
+    fn new_session(&mut self,
+                   msg: WebDriverMessage<U>,
+                   timeouts: Timeouts,
+                   prompt_behavior: UnhandledPromptBehavior)
+                   -> WebDriverResult<WebDriverResponse> {
+        let resp = try!(self.handler.handle_command(&self.session, msg));
+        if let WebDriverResponse::NewSession(ref new_session) = resp {
+            self.session = Some(Session::new(new_session.sessionId.clone(),
+                                             timeouts,
+                                             prompt_behavior));
+        }
+        Ok(resp)
+    }
+
+    fn get_timeouts(&self) -> WebDriverResult<WebDriverResponse> {
+        let session = try_opt!(self.session.as_ref(),
+                               ErrorStatus::InvalidSessionId,
+                               "Tried to run a command before creating a session");
+        Ok(WebDriverResponse::Timeouts(session.timeouts.to_response()))
+    }
+
+    /// The handler sees the new timeouts in the command parameters; they
+    /// are only stored in the session once the handler accepted them.
+    fn set_timeouts(&mut self, msg: WebDriverMessage<U>) -> WebDriverResult<WebDriverResponse> {
+        let mut timeouts = try_opt!(self.session.as_ref(),
+                                    ErrorStatus::InvalidSessionId,
+                                    "Tried to run a command before creating a session")
+            .timeouts.clone();
+        if let WebDriverCommand::SetTimeouts(ref parameters) = msg.command {
+            timeouts.update(parameters);
+        }
+
+        let resp = try!(self.handler.handle_command(&self.session, msg));
+        if let Some(ref mut session) = self.session {
+            session.timeouts = timeouts;
+        }
+        Ok(resp)
+    }
+
+    /// The input sources are added to the session's input state before the
+    /// handler runs the command, and their state is updated with the actions
+    /// once the handler performed them.
+    fn perform_actions(&mut self, msg: WebDriverMessage<U>) -> WebDriverResult<WebDriverResponse> {
+        let parameters = match msg.command {
+            WebDriverCommand::PerformActions(ref parameters) => parameters.clone(),
+            _ => unreachable!()
+        };
+        try!(try_opt!(self.session.as_mut(),
+                      ErrorStatus::InvalidSessionId,
+                      "Tried to run a command before creating a session")
+             .input_state.add_sources(&parameters));
+
+        let resp = try!(self.handler.handle_command(&self.session, msg));
+        if let Some(ref mut session) = self.session {
+            try!(session.input_state.update(parameters));
+        }
+        Ok(resp)
+    }
+
+    /// The actions to release the pressed keys and buttons are computed from
+    /// the session's input state and given to the handler, after which the
+    /// input state is reset.
+    fn release_actions(&mut self, msg: WebDriverMessage<U>) -> WebDriverResult<WebDriverResponse> {
+        let actions = try_opt!(self.session.as_ref(),
+                               ErrorStatus::InvalidSessionId,
+                               "Tried to run a command before creating a session")
+            .input_state.release_actions();
+        let resp = try!(self.handler.release_actions(&self.session, msg, actions));
+        if let Some(ref mut session) = self.session {
+            session.input_state = InputState::new();
+        }
+        Ok(resp)
+    }
+
     fn delete_session(&mut self) {
         debug!("Deleting session");
         self.handler.delete_session(&self.session);
         self.session = None;
     }
+}
+
+/// Process the capabilities of a new session, returning its initial timeouts
+/// and user prompt behavior.
+pub(crate) fn new_session_settings(capabilities: &CapabilitiesRegistry,
+                                   parameters: &mut NewSessionParameters)
+                                   -> WebDriverResult<(Timeouts, UnhandledPromptBehavior)> {
+    try!(capabilities.process(parameters));
+    Ok((try!(Timeouts::from_capabilities(parameters)),
+        try!(UnhandledPromptBehavior::from_capabilities(parameters))))
+}
 
-    fn check_session(&self, msg: &WebDriverMessage<U>) -> WebDriverResult<()> {
-        match msg.session_id {
-            Some(ref msg_session_id) => {
-                match self.session {
-                    Some(ref existing_session) => {
-                        if existing_session.id != *msg_session_id {
+/// Check that `msg` may run given the current session.
+pub(crate) fn check_session<U>(session: &Option<Session>, msg: &WebDriverMessage<U>)
+                               -> WebDriverResult<()>
+    where U: WebDriverExtensionRoute
+{
+    match msg.session_id {
+        Some(ref msg_session_id) => {
+            match *session {
+                Some(ref existing_session) => {
+                    if existing_session.id != *msg_session_id {
+                        Err(WebDriverError::new(
+                            ErrorStatus::InvalidSessionId,
+                            format!("Got unexpected session id {} expected {}",
+                                    msg_session_id,
+                                    existing_session.id)))
+                    } else {
+                        Ok(())
+                    }
+                },
+                None => Ok(())
+            }
+        },
+        None => {
+            match *session {
+                Some(_) => {
+                    match msg.command {
+                        WebDriverCommand::Status => Ok(()),
+                        WebDriverCommand::NewSession(_) => {
                             Err(WebDriverError::new(
-                                ErrorStatus::InvalidSessionId,
-                                format!("Got unexpected session id {} expected {}",
-                                        msg_session_id,
-                                        existing_session.id)))
-                        } else {
-                            Ok(())
-                        }
-                    },
-                    None => Ok(())
-                }
-            },
-            None => {
-                match self.session {
-                    Some(_) => {
-                        match msg.command {
-                            WebDriverCommand::Status => Ok(()),
-                            WebDriverCommand::NewSession(_) => {
-                                Err(WebDriverError::new(
-                                    ErrorStatus::SessionNotCreated,
-                                    "Session is already started"))
-                            },
-                            _ => {
-                                //This should be impossible
-                                error!("Got a message with no session id");
-                                Err(WebDriverError::new(
-                                    ErrorStatus::UnknownError,
-                                    "Got a command with no session?!"))
-                            }
-                        }
-                    },
-                    None => {
-                        match msg.command {
-                            WebDriverCommand::NewSession(_) => Ok(()),
-                            WebDriverCommand::Status => Ok(()),
-                            _ => Err(WebDriverError::new(
-                                ErrorStatus::InvalidSessionId,
-                                "Tried to run a command before creating a session"))
+                                ErrorStatus::SessionNotCreated,
+                                "Session is already started"))
+                        },
+                        _ => {
+                            //This should be impossible
+                            error!("Got a message with no session id");
+                            Err(WebDriverError::new(
+                                ErrorStatus::UnknownError,
+                                "Got a command with no session?!"))
                         }
                     }
+                },
+                None => {
+                    match msg.command {
+                        WebDriverCommand::NewSession(_) => Ok(()),
+                        WebDriverCommand::Status => Ok(()),
+                        _ => Err(WebDriverError::new(
+                            ErrorStatus::InvalidSessionId,
+                            "Tried to run a command before creating a session"))
+                    }
                 }
             }
         }
     }
 }
 
+/// Whether user prompts are handled before running `command`.
+///
+/// Extension commands are left to the handler.
+pub(crate) fn handles_user_prompts<T: WebDriverExtensionCommand>(command: &WebDriverCommand<T>) -> bool {
+    match *command {
+        WebDriverCommand::NewSession(_) |
+        WebDriverCommand::DeleteSession |
+        WebDriverCommand::Status |
+        WebDriverCommand::GetTimeouts |
+        WebDriverCommand::SetTimeouts(_) |
+        WebDriverCommand::GetWindowHandle |
+        WebDriverCommand::GetWindowHandles |
+        WebDriverCommand::SwitchToWindow(_) |
+        WebDriverCommand::DismissAlert |
+        WebDriverCommand::AcceptAlert |
+        WebDriverCommand::GetAlertText |
+        WebDriverCommand::SendAlertText(_) |
+        WebDriverCommand::Extension(_) => false,
+        _ => true,
+    }
+}
+
+/// Whether `behavior` reports the text of the prompt it handles.
+pub(crate) fn needs_prompt_text(behavior: &UnhandledPromptBehavior) -> bool {
+    match *behavior {
+        UnhandledPromptBehavior::AcceptAndNotify |
+        UnhandledPromptBehavior::DismissAndNotify |
+        UnhandledPromptBehavior::Ignore => true,
+        _ => false,
+    }
+}
+
+/// The text of the prompt from the response to `GetAlertText`, or `None` if
+/// the prompt was closed in the meantime.
+pub(crate) fn prompt_text(resp: WebDriverResult<WebDriverResponse>)
+                          -> WebDriverResult<Option<String>> {
+    match resp {
+        Ok(WebDriverResponse::Generic(ref x)) => {
+            Ok(Some(x.value.as_string().unwrap_or("").to_string()))
+        },
+        Ok(_) => Ok(Some(String::new())),
+        Err(ref err) if err.error == ErrorStatus::NoSuchAlert => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+/// The command closing the prompt for `behavior`, if any.
+pub(crate) fn prompt_command<T>(behavior: &UnhandledPromptBehavior) -> Option<WebDriverCommand<T>>
+    where T: WebDriverExtensionCommand
+{
+    match *behavior {
+        UnhandledPromptBehavior::Accept |
+        UnhandledPromptBehavior::AcceptAndNotify => Some(WebDriverCommand::AcceptAlert),
+        UnhandledPromptBehavior::Dismiss |
+        UnhandledPromptBehavior::DismissAndNotify => Some(WebDriverCommand::DismissAlert),
+        UnhandledPromptBehavior::Ignore => None,
+    }
+}
+
+/// Check the response to the command closing the prompt, which may already
+/// have been closed.
+pub(crate) fn prompt_closed(resp: WebDriverResult<WebDriverResponse>) -> WebDriverResult<()> {
+    match resp {
+        Ok(_) => Ok(()),
+        Err(ref err) if err.error == ErrorStatus::NoSuchAlert => Ok(()),
+        Err(err) => Err(err),
+    }
+}
+
+/// The outcome of the command once the prompt was handled with `behavior`.
+pub(crate) fn prompt_result(behavior: &UnhandledPromptBehavior, text: String)
+                            -> WebDriverResult<()> {
+    match *behavior {
+        UnhandledPromptBehavior::Accept |
+        UnhandledPromptBehavior::Dismiss => Ok(()),
+        _ => {
+            let mut data = BTreeMap::new();
+            data.insert("text".to_owned(), text.to_json());
+            let message = format!("Unexpected alert open: {}", text);
+            Err(WebDriverError::new(ErrorStatus::UnexpectedAlertOpen, message)
+                .with_data(Json::Object(data)))
+        }
+    }
+}
+
 struct HttpHandler<U: WebDriverExtensionRoute> {
     chan: Mutex<Sender<DispatchMessage<U>>>,
-    api: Mutex<WebDriverHttpApi<U>>
+    api: Mutex<WebDriverHttpApi<U>>,
+    backtrace_capture: BacktraceCapture,
+    stacktrace_reporting: StacktraceReporting,
 }
 
 impl <U: WebDriverExtensionRoute> HttpHandler<U> {
-    fn new(api: WebDriverHttpApi<U>, chan: Sender<DispatchMessage<U>>) -> HttpHandler<U> {
+    fn new(api: WebDriverHttpApi<U>,
+           chan: Sender<DispatchMessage<U>>,
+           config: &ServerConfig) -> HttpHandler<U> {
         HttpHandler {
             chan: Mutex::new(chan),
-            api: Mutex::new(api)
+            api: Mutex::new(api),
+            backtrace_capture: config.backtrace_capture,
+            stacktrace_reporting: config.stacktrace_reporting,
         }
     }
+
+    fn encode_error(&self, err: WebDriverError) -> (StatusCode, String) {
+        let err = err.with_backtrace_capture(self.backtrace_capture);
+        (err.http_status(), err.to_json_with(self.stacktrace_reporting).to_string())
+    }
 }
 
 impl <U: WebDriverExtensionRoute> Handler for HttpHandler<U> {
     fn handle(&self, req: Request, res: Response) {
+        set_backtrace_capture(self.backtrace_capture);
         let mut req = req;
         let mut res = res;
 
@@ -208,14 +632,12 @@ impl <U: WebDriverExtensionRoute> Handler for HttpHandler<U> {
                         match recv_res.recv() {
                             Ok(data) => match data {
                                 Ok(response) => (StatusCode::Ok, response.to_json_string()),
-                                Err(err) => (err.http_status(), err.to_json_string()),
+                                Err(err) => self.encode_error(err),
                             },
                             Err(e) => panic!("Error reading response: {:?}", e)
                         }
                     },
-                    Err(err) => {
-                        (err.http_status(), err.to_json_string())
-                    }
+                    Err(err) => self.encode_error(err)
                 };
                 debug!("Returning status {:?}", status);
                 debug!("Returning body {}", resp_body);
@@ -240,19 +662,349 @@ pub fn start<T, U>(address: SocketAddr,
                    -> Result<Listening>
     where T: 'static + WebDriverHandler<U>,
           U: 'static + WebDriverExtensionRoute
+{
+    start_with_config(address, handler, extension_routes, ServerConfig::default())
+}
+
+pub fn start_with_config<T, U>(address: SocketAddr,
+                               handler: T,
+                               extension_routes: &[(Method, &str, U)],
+                               config: ServerConfig)
+                               -> Result<Listening>
+    where T: 'static + WebDriverHandler<U>,
+          U: 'static + WebDriverExtensionRoute
 {
     let (msg_send, msg_recv) = channel();
 
     let api = WebDriverHttpApi::new(extension_routes);
-    let http_handler = HttpHandler::new(api, msg_send);
+    let http_handler = HttpHandler::new(api, msg_send, &config);
     let mut server = try!(Server::http(address));
     server.keep_alive(None);
 
     let builder = thread::Builder::new().name("webdriver dispatcher".to_string());
     try!(builder.spawn(move || {
-        let mut dispatcher = Dispatcher::new(handler);
+        set_backtrace_capture(config.backtrace_capture);
+        let mut dispatcher = Dispatcher::new(handler, config);
         dispatcher.run(msg_recv);
     }));
 
     server.handle(http_handler)
 }
+
+/// A server started by a test, which stops listening when dropped.
+#[cfg(test)]
+pub(crate) struct TestServer {
+    listening: Listening,
+}
+
+#[cfg(test)]
+impl TestServer {
+    pub(crate) fn start<T>(handler: T) -> TestServer
+        where T: 'static + WebDriverHandler
+    {
+        let address = "127.0.0.1:0".parse().unwrap();
+        let listening = start(address, handler,
+                              &[] as &[(_, _, VoidWebDriverExtensionRoute)]).unwrap();
+        TestServer { listening: listening }
+    }
+
+    pub(crate) fn url(&self) -> String {
+        format!("http://{}", self.listening.socket)
+    }
+}
+
+#[cfg(test)]
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        // Dropping `Listening` without closing it waits for the server to
+        // exit, which it never does.
+        self.listening.close().unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use command::{WebDriverCommand, WebDriverMessage, Parameters, NewSessionParameters,
+                  TimeoutsParameters, LocatorParameters, VoidWebDriverExtensionCommand,
+                  ActionsType, KeyAction, KeyActionItem};
+    use common::{LocatorStrategy, Nullable};
+    use error::{WebDriverResult, WebDriverError, ErrorStatus};
+    use httpapi::VoidWebDriverExtensionRoute;
+    use input::{InputSourceState, InputState, SourceAction};
+    use response::{NewSessionResponse, ValueResponse, WebDriverResponse};
+    use rustc_serialize::json::{Json, ToJson};
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+    use super::{Clock, Dispatcher, ImplicitWait, ServerConfig, Session, WebDriverHandler};
+
+    #[derive(Default)]
+    struct TestHandler {
+        find_calls: usize,
+        // The call to FindElement that first finds an element
+        find_after: usize,
+        prompt: Option<String>,
+        prompt_action: Option<&'static str>,
+        released: Vec<String>,
+        perform_calls: usize,
+        // A key whose key down fails
+        failing_key: Option<char>,
+    }
+
+    impl WebDriverHandler for TestHandler {
+        fn handle_command(&mut self, _: &Option<Session>, msg: WebDriverMessage)
+                          -> WebDriverResult<WebDriverResponse> {
+            match msg.command {
+                WebDriverCommand::NewSession(_) => Ok(WebDriverResponse::NewSession(
+                    NewSessionResponse::new("test".into(), Json::Null))),
+                WebDriverCommand::FindElement(_) => {
+                    self.find_calls += 1;
+                    if self.find_calls < self.find_after {
+                        Err(WebDriverError::new(ErrorStatus::NoSuchElement, "Not found"))
+                    } else {
+                        Ok(WebDriverResponse::Generic(ValueResponse::new("element".to_json())))
+                    }
+                },
+                WebDriverCommand::GetAlertText => {
+                    let text = self.prompt.clone().unwrap();
+                    Ok(WebDriverResponse::Generic(ValueResponse::new(text.to_json())))
+                },
+                WebDriverCommand::AcceptAlert => {
+                    self.prompt = None;
+                    self.prompt_action = Some("accept");
+                    Ok(WebDriverResponse::Void)
+                },
+                WebDriverCommand::DismissAlert => {
+                    self.prompt = None;
+                    self.prompt_action = Some("dismiss");
+                    Ok(WebDriverResponse::Void)
+                },
+                WebDriverCommand::PerformActions(ref parameters) => {
+                    self.perform_calls += 1;
+                    let fails = parameters.actions.iter().any(|x| match x.actions {
+                        ActionsType::Key(ref actions) => actions.iter().any(|x| match *x {
+                            KeyActionItem::Key(KeyAction::Down(ref x)) => {
+                                Some(x.value) == self.failing_key
+                            },
+                            _ => false,
+                        }),
+                        _ => false,
+                    });
+                    if fails {
+                        Err(WebDriverError::new(ErrorStatus::UnknownError, "Key down failed"))
+                    } else {
+                        Ok(WebDriverResponse::Void)
+                    }
+                },
+                _ => Ok(WebDriverResponse::Void),
+            }
+        }
+
+        fn delete_session(&mut self, _: &Option<Session>) {}
+
+        fn release_actions(&mut self, _: &Option<Session>, _: WebDriverMessage,
+                           actions: Vec<SourceAction>) -> WebDriverResult<WebDriverResponse> {
+            self.released = actions.into_iter().map(|x| x.id).collect();
+            Ok(WebDriverResponse::Void)
+        }
+
+        fn is_user_prompt_open(&mut self, _: &Option<Session>) -> WebDriverResult<bool> {
+            Ok(self.prompt.is_some())
+        }
+    }
+
+    struct FakeClock {
+        start: Instant,
+        elapsed: Arc<Mutex<Duration>>,
+    }
+
+    impl Clock for FakeClock {
+        fn now(&self) -> Instant {
+            self.start + *self.elapsed.lock().unwrap()
+        }
+
+        fn sleep(&self, duration: Duration) {
+            *self.elapsed.lock().unwrap() += duration;
+        }
+    }
+
+    fn new_session(dispatcher: &mut Dispatcher<TestHandler, VoidWebDriverExtensionRoute>,
+                   capabilities: &str) {
+        let parameters = NewSessionParameters::from_json(&Json::from_str(capabilities).unwrap());
+        dispatcher.dispatch(WebDriverMessage::new(
+            None, WebDriverCommand::NewSession(parameters.unwrap()))).unwrap();
+    }
+
+    fn command(command: WebDriverCommand<VoidWebDriverExtensionCommand>)
+               -> WebDriverMessage {
+        WebDriverMessage::new(Some("test".into()), command)
+    }
+
+    #[test]
+    fn test_timeouts() {
+        let mut dispatcher = Dispatcher::new(TestHandler::default(), ServerConfig::default());
+        new_session(&mut dispatcher,
+                    r#"{"desiredCapabilities": {"timeouts": {"implicit": 100}}}"#);
+
+        let parameters = TimeoutsParameters::from_json(
+            &Json::from_str(r#"{"script": null, "pageLoad": 1000}"#).unwrap()).unwrap();
+        dispatcher.dispatch(command(WebDriverCommand::SetTimeouts(parameters))).unwrap();
+
+        match dispatcher.dispatch(command(WebDriverCommand::GetTimeouts)).unwrap() {
+            WebDriverResponse::Timeouts(timeouts) => {
+                assert_eq!(timeouts.script, Nullable::Null);
+                assert_eq!(timeouts.pageLoad, 1000);
+                assert_eq!(timeouts.implicit, 100);
+            },
+            _ => panic!("Expected a timeouts response")
+        }
+    }
+
+    fn perform_actions(body: &str) -> WebDriverMessage {
+        let parameters = Parameters::from_json(&Json::from_str(body).unwrap()).unwrap();
+        command(WebDriverCommand::PerformActions(parameters))
+    }
+
+    #[test]
+    fn test_input_state() {
+        let mut dispatcher = Dispatcher::new(TestHandler::default(), ServerConfig::default());
+        new_session(&mut dispatcher, "{}");
+
+        dispatcher.dispatch(perform_actions(r#"{"actions": [
+            {"type": "key", "id": "keyboard", "actions": [
+                {"type": "keyDown", "value": "a"}, {"type": "pause"}]},
+            {"type": "pointer", "id": "mouse", "actions": [
+                {"type": "pause"}, {"type": "pointerDown", "button": 0}]}]}"#)).unwrap();
+        let err = dispatcher.dispatch(perform_actions(r#"{"actions": [
+            {"type": "pointer", "id": "keyboard", "actions": []}]}"#)).err().unwrap();
+        assert_eq!(err.error, ErrorStatus::InvalidArgument);
+        assert!(dispatcher.session.as_ref().unwrap().input_state.source("mouse").is_some());
+
+        dispatcher.dispatch(command(WebDriverCommand::ReleaseActions)).unwrap();
+        assert_eq!(dispatcher.handler.released, vec!["mouse", "keyboard"]);
+        assert_eq!(dispatcher.session.as_ref().unwrap().input_state, InputState::new());
+
+        // Invalid ids are rejected before any action is performed
+        let calls = dispatcher.handler.perform_calls;
+        let err = dispatcher.dispatch(perform_actions(r#"{"actions": [
+            {"type": "key", "id": "keyboard", "actions": [{"type": "keyDown", "value": "a"}]},
+            {"type": "key", "id": "keyboard", "actions": []}]}"#)).err().unwrap();
+        assert_eq!(err.error, ErrorStatus::InvalidArgument);
+        assert_eq!(dispatcher.handler.perform_calls, calls);
+        assert_eq!(dispatcher.session.as_ref().unwrap().input_state, InputState::new());
+    }
+
+    #[test]
+    fn test_input_state_failed_actions() {
+        let mut dispatcher = Dispatcher::new(TestHandler {
+            failing_key: Some('c'),
+            ..TestHandler::default()
+        }, ServerConfig::default());
+        new_session(&mut dispatcher, "{}");
+
+        dispatcher.dispatch(perform_actions(r#"{"actions": [
+            {"type": "key", "id": "keyboard", "actions": [{"type": "keyDown", "value": "a"}]}]}"#))
+            .unwrap();
+
+        // The state isn't updated with the actions of a command that failed
+        let err = dispatcher.dispatch(perform_actions(r#"{"actions": [
+            {"type": "key", "id": "keyboard", "actions": [
+                {"type": "keyDown", "value": "b"},
+                {"type": "keyDown", "value": "c"}]},
+            {"type": "pointer", "id": "mouse", "actions": [
+                {"type": "pointerDown", "button": 0}]}]}"#)).err().unwrap();
+        assert_eq!(err.error, ErrorStatus::UnknownError);
+        assert_eq!(dispatcher.handler.perform_calls, 2);
+        let input_state = &dispatcher.session.as_ref().unwrap().input_state;
+        match input_state.source("keyboard") {
+            Some(&InputSourceState::Key(ref x)) => {
+                assert_eq!(x.pressed.iter().cloned().collect::<String>(), "a");
+            },
+            _ => panic!("Expected a key input source")
+        }
+        match input_state.source("mouse") {
+            Some(&InputSourceState::Pointer(ref x)) => assert!(x.pressed.is_empty()),
+            _ => panic!("Expected a pointer input source")
+        }
+
+        dispatcher.dispatch(command(WebDriverCommand::ReleaseActions)).unwrap();
+        assert_eq!(dispatcher.handler.released, vec!["keyboard"]);
+    }
+
+    fn implicit_wait_dispatcher(find_after: usize)
+                                -> (Dispatcher<TestHandler, VoidWebDriverExtensionRoute>,
+                                    Arc<Mutex<Duration>>) {
+        let elapsed = Arc::new(Mutex::new(Duration::from_millis(0)));
+        let config = ServerConfig {
+            implicit_wait: Some(ImplicitWait {
+                poll_interval: Duration::from_millis(300),
+                clock: Box::new(FakeClock { start: Instant::now(), elapsed: elapsed.clone() }),
+            }),
+            ..Default::default()
+        };
+        let handler = TestHandler { find_after: find_after, ..Default::default() };
+        let mut dispatcher = Dispatcher::new(handler, config);
+        new_session(&mut dispatcher,
+                    r#"{"desiredCapabilities": {"timeouts": {"implicit": 1000}}}"#);
+        (dispatcher, elapsed)
+    }
+
+    fn find_element() -> WebDriverMessage {
+        command(WebDriverCommand::FindElement(LocatorParameters {
+            using: LocatorStrategy::CSSSelector,
+            value: "p".into(),
+        }))
+    }
+
+    #[test]
+    fn test_implicit_wait_found() {
+        let (mut dispatcher, elapsed) = implicit_wait_dispatcher(3);
+        assert!(dispatcher.dispatch(find_element()).is_ok());
+        assert_eq!(dispatcher.handler.find_calls, 3);
+        assert_eq!(*elapsed.lock().unwrap(), Duration::from_millis(600));
+    }
+
+    #[test]
+    fn test_implicit_wait_timeout() {
+        let (mut dispatcher, elapsed) = implicit_wait_dispatcher(10);
+        let err = dispatcher.dispatch(find_element()).err().unwrap();
+        assert_eq!(err.error, ErrorStatus::NoSuchElement);
+        // Polls at 0, 300, 600, 900 and finally at the 1000ms deadline
+        assert_eq!(dispatcher.handler.find_calls, 5);
+        assert_eq!(*elapsed.lock().unwrap(), Duration::from_millis(1000));
+    }
+
+    fn prompt_dispatcher(behavior: &str)
+                         -> Dispatcher<TestHandler, VoidWebDriverExtensionRoute> {
+        let mut dispatcher = Dispatcher::new(TestHandler::default(), ServerConfig::default());
+        new_session(&mut dispatcher, &format!(
+            r#"{{"desiredCapabilities": {{"unhandledPromptBehavior": "{}"}}}}"#, behavior));
+        dispatcher.handler.prompt = Some("Are you sure?".into());
+        dispatcher
+    }
+
+    #[test]
+    fn test_prompt_accept() {
+        let mut dispatcher = prompt_dispatcher("accept");
+        assert!(dispatcher.dispatch(command(WebDriverCommand::GetTitle)).is_ok());
+        assert_eq!(dispatcher.handler.prompt_action, Some("accept"));
+    }
+
+    #[test]
+    fn test_prompt_dismiss_and_notify() {
+        let mut dispatcher = prompt_dispatcher("dismiss and notify");
+        let err = dispatcher.dispatch(command(WebDriverCommand::GetTitle)).err().unwrap();
+        assert_eq!(err.error, ErrorStatus::UnexpectedAlertOpen);
+        assert_eq!(err.data.as_ref().and_then(|x| x.find("text")),
+                   Some(&"Are you sure?".to_json()));
+        assert_eq!(dispatcher.handler.prompt_action, Some("dismiss"));
+    }
+
+    #[test]
+    fn test_prompt_ignore() {
+        let mut dispatcher = prompt_dispatcher("ignore");
+        let err = dispatcher.dispatch(command(WebDriverCommand::GetTitle)).err().unwrap();
+        assert_eq!(err.error, ErrorStatus::UnexpectedAlertOpen);
+        assert_eq!(dispatcher.handler.prompt_action, None);
+        assert!(dispatcher.dispatch(command(WebDriverCommand::GetAlertText)).is_ok());
+    }
+}